@@ -6,9 +6,7 @@ extern crate test;
 use indolentjson::compact::*;
 use test::{black_box, Bencher};
 
-#[bench]
-fn benchmark_compact(b : &mut Bencher) {
-    let test_string = black_box(r#"{
+const TEST_STRING: &str = r#"{
         "A longish bit of JSON": true,
         "containing": {
             "whitespace": " ",
@@ -19,8 +17,32 @@ fn benchmark_compact(b : &mut Bencher) {
             "numbers": [0, 1, 1e4, 1.0, -1.0e7 ],
             "and more": [ true, false, null ]
         }
-    }"#.as_bytes());
+    }"#;
+
+#[bench]
+fn benchmark_compact(b : &mut Bencher) {
+    let test_string = black_box(TEST_STRING.as_bytes());
     let mut output : Vec<u8> = Vec::with_capacity(test_string.len());
     b.bytes = test_string.len() as u64;
     b.iter(|| { output.clear(); compact(test_string, &mut output) });
 }
+
+#[bench]
+fn benchmark_compact_with_map(b : &mut Bencher) {
+    let test_string = black_box(TEST_STRING.as_bytes());
+    let mut output : Vec<u8> = Vec::with_capacity(test_string.len());
+    let mut map = OffsetMap::new();
+    b.bytes = test_string.len() as u64;
+    b.iter(|| {
+        output.clear();
+        compact_with_map(test_string, &mut output, &mut map)
+    });
+}
+
+#[bench]
+fn benchmark_compact_ascii(b : &mut Bencher) {
+    let test_string = black_box(TEST_STRING.as_bytes());
+    let mut output : Vec<u8> = Vec::with_capacity(test_string.len());
+    b.bytes = test_string.len() as u64;
+    b.iter(|| { output.clear(); compact_ascii(test_string, &mut output) });
+}
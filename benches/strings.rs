@@ -58,3 +58,14 @@ fn unescape_mixed(b: &mut Bencher) {
     b.bytes = test_string.len() as u64;
     b.iter(|| { unescape_bytes(test_string) });
 }
+
+#[bench]
+fn unescape_mixed_reused_buffer(b: &mut Bencher) {
+    let test_string = black_box(
+        br#"This\nIsA\tMixture\u0000OfStrings\fThat\"Need\\Escaping"#
+    );
+    let mut decoder = StringDecoder::new();
+
+    b.bytes = test_string.len() as u64;
+    b.iter(|| { decoder.clear(); decoder.decode(test_string).unwrap() });
+}
@@ -16,10 +16,9 @@ extern crate indolentjson;
 
 use indolentjson::compact::*;
 use indolentjson::parse::*;
+use indolentjson::value::*;
 
-use std::str;
-
-const TEST_STRING : &'static [u8] = br#"{
+const TEST_STRING : &[u8] = br#"{
     "A longish bit of JSON": true,
     "containing": {
         "whitespace": " ",
@@ -34,57 +33,42 @@ const TEST_STRING : &'static [u8] = br#"{
 
 /// Enumerates the given nodes and prints their types.
 fn enumerate_and_print(compacted: &[u8], nodes: &[Node]) {
-    enumerate_internal(compacted, nodes.len(), &mut nodes.iter(), 0, false);
+    if let Some(root) = ValueRef::root(compacted, nodes) {
+        print_value(root, 0);
+    }
 }
 
 
-fn enumerate_internal<'a, I>(compacted: &[u8], iterations: usize, nodes: &mut I, depth: usize, is_object: bool)
-    where I: Iterator<Item=&'a Node>
-{
-    let mut offset = 0;
-    for idx in 0..iterations {
-        let node = match nodes.next() {
-            Some(n) => n,
-            None => return,
-        };
-
-        let size_of_node = node.length_in_bytes as usize;
-
-        if !is_object || idx % 2 == 0 {
-            print!("{}", &"\t\t\t\t\t\t\t"[..depth]);
-        }
-
-        if node.children > 0 {
-            println!("{} ", match compacted[offset] {
-                b'{' => "object ->",
-                b'[' => "array ->",
-                _ => unreachable!(),  // Only arrays or objects can have children
-            });
-            enumerate_internal(
-                &compacted[offset+1..],
-                node.children as usize,
-                nodes,
-                depth + 1,
-                compacted[offset] == b'{',
-            );
-        } else {
-            print!("{} ", match compacted[offset] {
-                b't' => "TRUE",
-                b'f' => "FALSE",
-                b'n' => "NULL",
-                b'"' => "string",
-                b'{' => "object (empty)",
-                b'[' => "array (empty)",
-                b'-' | b'0'...b'9' => "number",
-                _ => unreachable!(),
-            });
-
-            if !is_object || idx % 2 == 1 {
-                println!("");
+fn print_value(value: ValueRef, depth: usize) {
+    if value.is_empty_container() {
+        println!("{} ", match value.kind() {
+            JsonKind::Object => "object (empty)",
+            _ => "array (empty)",
+        });
+        return;
+    }
+    match value.kind() {
+        JsonKind::Object => {
+            println!("object -> ");
+            for (_, member) in value.object_iter().unwrap() {
+                print!("{}string ", &"\t\t\t\t\t\t\t"[..depth + 1]);
+                print_value(member, depth + 1);
             }
-        }
-
-        offset += size_of_node + 1;
+        },
+        JsonKind::Array => {
+            println!("array -> ");
+            for element in value.array_iter().unwrap() {
+                print!("{}", &"\t\t\t\t\t\t\t"[..depth + 1]);
+                print_value(element, depth + 1);
+            }
+        },
+        JsonKind::String => println!("string "),
+        JsonKind::Number => println!("number "),
+        JsonKind::Bool => println!("{} ", match value.raw_bytes()[0] {
+            b't' => "TRUE",
+            _ => "FALSE",
+        }),
+        JsonKind::Null => println!("NULL "),
     }
 }
 
@@ -0,0 +1,175 @@
+use std::cell::{Cell, RefCell};
+
+use value::{JsonKind, ValueRef};
+
+
+/// A side index over the elements of a JSON array which makes looking up an
+/// element by position and getting the length of the array O(1).
+///
+/// Without an index finding element N of an array means skipping over the N
+/// elements before it. The index stores the node index and byte offset of
+/// each element so the skipping only needs to be done once.
+///
+/// The index can either be built eagerly using `ArrayIndex::new`, or lazily
+/// using `ArrayIndex::lazy`. A lazy index only walks as far into the array as
+/// the largest position that has been looked up so far, and remembers where
+/// it got to.
+pub struct ArrayIndex<'a> {
+    array: ValueRef<'a>,
+    entries: RefCell<Vec<Entry>>,
+    complete: Cell<bool>,
+}
+
+struct Entry {
+    index: u32,
+    offset: u32,
+}
+
+impl<'a> ArrayIndex<'a> {
+    /// Build an index of every element in the array. Returns `None` if the
+    /// value isn't an array.
+    pub fn new(array: ValueRef<'a>) -> Option<ArrayIndex<'a>> {
+        let index = ArrayIndex::lazy(array)?;
+        index.extend(None);
+        Some(index)
+    }
+
+    /// Create an index which is filled in as elements are looked up.
+    /// Returns `None` if the value isn't an array.
+    pub fn lazy(array: ValueRef<'a>) -> Option<ArrayIndex<'a>> {
+        if array.kind() != JsonKind::Array {
+            return None;
+        }
+        Some(ArrayIndex {
+            array,
+            entries: RefCell::new(Vec::new()),
+            complete: Cell::new(false),
+        })
+    }
+
+    /// The array this is an index of.
+    pub fn array(&self) -> ValueRef<'a> {
+        self.array
+    }
+
+    /// The number of elements in the array.
+    pub fn len(&self) -> usize {
+        self.extend(None);
+        self.entries.borrow().len()
+    }
+
+    /// Whether the array has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.array.children_count() == 0
+    }
+
+    /// Get the element at a position in the array. Returns `None` if the
+    /// array is too short.
+    pub fn get(&self, position: usize) -> Option<ValueRef<'a>> {
+        self.extend(Some(position));
+        let entries = self.entries.borrow();
+        let entry = entries.get(position)?;
+        Some(ValueRef::new(
+            self.array.document_bytes(),
+            self.array.document_nodes(),
+            entry.index as usize,
+            entry.offset as usize,
+        ))
+    }
+
+    /// Walk the array until the index includes the element at `position`,
+    /// or until the end of the array if the position is `None`.
+    fn extend(&self, position: Option<usize>) {
+        if self.complete.get() {
+            return;
+        }
+        let mut entries = self.entries.borrow_mut();
+        if let Some(position) = position {
+            if position < entries.len() {
+                return;
+            }
+        }
+        let nodes = self.array.document_nodes();
+        // Resume from the element after the last one in the index, or from
+        // the first element if the index is empty.
+        let (mut index, mut offset) = match entries.last() {
+            Some(last) => {
+                let node = &nodes[last.index as usize];
+                (
+                    last.index as usize + node.children as usize + 1,
+                    last.offset as usize + node.length_in_bytes as usize + 1,
+                )
+            },
+            None => (self.array.index() + 1, self.array.offset() + 1),
+        };
+        let end = self.array.index() + 1 + self.array.children_count();
+        while index < end {
+            if let Some(position) = position {
+                if position < entries.len() {
+                    return;
+                }
+            }
+            entries.push(Entry { index: index as u32, offset: offset as u32 });
+            let node = &nodes[index];
+            index += node.children as usize + 1;
+            offset += node.length_in_bytes as usize + 1;
+        }
+        self.complete.set(true);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::ArrayIndex;
+    use document::Document;
+
+    #[test]
+    fn array_index_eager() {
+        let document = Document::from_slice(br#"[1,[2,[3]],{"a":4},"5"]"#).unwrap();
+        let index = ArrayIndex::new(document.root().unwrap()).unwrap();
+        assert_eq!(4, index.len());
+        assert!(!index.is_empty());
+        assert_eq!(b"1", index.get(0).unwrap().raw_bytes());
+        assert_eq!(b"[2,[3]]", index.get(1).unwrap().raw_bytes());
+        assert_eq!(br#"{"a":4}"#, index.get(2).unwrap().raw_bytes());
+        assert_eq!(br#""5""#, index.get(3).unwrap().raw_bytes());
+        assert!(index.get(4).is_none());
+    }
+
+    #[test]
+    fn array_index_lazy() {
+        let document = Document::from_slice(br#"[0,[1,[1]],2,{"3":3},4,5,6,7,8,"9"]"#).unwrap();
+        let root = document.root().unwrap();
+        let eager = ArrayIndex::new(root).unwrap();
+        // Look up positions out of order so the lazy index has to resume
+        // from where it got to, and look some up more than once.
+        let lazy = ArrayIndex::lazy(root).unwrap();
+        for &position in &[3, 1, 7, 7, 0, 10, 9, 2, 11, 5] {
+            let expected = eager.get(position);
+            let found = lazy.get(position);
+            assert_eq!(expected.is_some(), found.is_some());
+            if let (Some(expected), Some(found)) = (expected, found) {
+                assert_eq!(expected.index(), found.index());
+                assert_eq!(expected.offset(), found.offset());
+                assert_eq!(root.get_index(position).unwrap().index(), found.index());
+            }
+        }
+        assert_eq!(br#"{"3":3}"#, lazy.get(3).unwrap().raw_bytes());
+        assert_eq!(br#""9""#, lazy.get(9).unwrap().raw_bytes());
+        assert!(lazy.get(10).is_none());
+        assert_eq!(10, lazy.len());
+        assert_eq!(eager.len(), ArrayIndex::lazy(root).unwrap().len());
+    }
+
+    #[test]
+    fn array_index_not_array() {
+        let document = Document::from_slice(br#"{"a":[]}"#).unwrap();
+        let root = document.root().unwrap();
+        assert!(ArrayIndex::new(root).is_none());
+        let index = ArrayIndex::new(root.get("a").unwrap()).unwrap();
+        assert_eq!(0, index.len());
+        assert!(index.is_empty());
+        assert!(index.get(0).is_none());
+    }
+}
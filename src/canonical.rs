@@ -0,0 +1,569 @@
+use compact::{compact_with_map, OffsetMap};
+use error::{Error, ErrorKind};
+use number::{parse_f64, parse_i64, NumberError};
+use parse::parse;
+use std::cmp::Ordering;
+use std::str;
+use std::vec;
+use strings::{escaped_cmp, unescape};
+use validate::{validate, MAX_SAFE_INTEGER};
+use value::{ArrayIter, JsonKind, ValueRef};
+
+
+/// Convert JSON to the canonical form that Matrix uses when signing and
+/// hashing events. The JSON is compacted, object keys are sorted by the code
+/// points of their decoded contents, and numbers are written as plain
+/// integers. The output is appended to `output`.
+///
+/// Fails if the input isn't valid JSON or isn't valid UTF-8, if an object has
+/// the same key more than once, or if a number isn't an integer in the range
+/// `-MAX_SAFE_INTEGER` to `MAX_SAFE_INTEGER`. Numbers written with a fraction
+/// or an exponent, like `1.0` or `1e10`, are rejected as they are by
+/// `validate::validate_integers`, and so is `-0`. The offset of the error is
+/// the offset in the input, and nothing is written to the output.
+pub fn canonicalize(input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    canonicalize_with(input, output, &Matrix)
+}
+
+/// Convert JSON to the RFC 8785 JSON Canonicalization Scheme. The JSON is
+/// compacted, object keys are sorted by the UTF-16 code units of their
+/// decoded contents, and numbers are written the way ECMAScript's
+/// `Number.prototype.toString` writes the nearest IEEE double. The output is
+/// appended to `output`.
+///
+/// Fails if the input isn't valid JSON or isn't valid UTF-8, if an object has
+/// the same key more than once, or if a number is too large to be a double.
+/// The offset of the error is the offset in the input, and nothing is
+/// written to the output.
+pub fn canonicalize_jcs(input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    canonicalize_with(input, output, &Jcs)
+}
+
+
+/// The differences between the canonical forms. Both write strings the
+/// way `compact` does, apart from the case of hex digits.
+trait Scheme {
+    /// Order two object keys, given their escaped contents.
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> Ordering;
+    /// Write a number, or fail if it can't be represented.
+    fn write_number(&self, value: ValueRef, output: &mut Vec<u8>) -> Result<(), Error>;
+}
+
+/// Matrix canonical JSON, see `canonicalize`.
+struct Matrix;
+
+impl Scheme for Matrix {
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> Ordering {
+        escaped_cmp(a, b)
+    }
+
+    fn write_number(&self, value: ValueRef, output: &mut Vec<u8>) -> Result<(), Error> {
+        write_integer(value, output)
+    }
+}
+
+/// RFC 8785 JSON Canonicalization Scheme, see `canonicalize_jcs`.
+struct Jcs;
+
+impl Scheme for Jcs {
+    fn compare_keys(&self, a: &[u8], b: &[u8]) -> Ordering {
+        // The keys have been validated so decoding won't fail. Compacted keys
+        // rarely contain escapes so this rarely allocates.
+        match (unescape(a), unescape(b)) {
+            (Ok(a), Ok(b)) => a.encode_utf16().cmp(b.encode_utf16()),
+            _ => a.cmp(b),
+        }
+    }
+
+    fn write_number(&self, value: ValueRef, output: &mut Vec<u8>) -> Result<(), Error> {
+        match parse_f64(value.raw_bytes()) {
+            Ok(number) => {
+                write_es6_number(number, output);
+                Ok(())
+            },
+            Err(NumberError::Overflow) => {
+                Err(Error::new(ErrorKind::NumberOutOfRange, value.offset()))
+            },
+            Err(_) => Err(Error::new(ErrorKind::InvalidNumber, value.offset())),
+        }
+    }
+}
+
+fn canonicalize_with<S: Scheme>(input: &[u8], output: &mut Vec<u8>, scheme: &S) -> Result<(), Error> {
+    check_whitespace(input)?;
+    let mut compacted = Vec::with_capacity(input.len());
+    let mut map = OffsetMap::new();
+    compact_with_map(input, &mut compacted, &mut map)?;
+    // Errors after compacting have offsets in the compacted JSON.
+    let in_input = |error: Error| {
+        Error::new(error.kind(), map.original_offset(error.offset()))
+    };
+    let mut nodes = Vec::new();
+    parse(&compacted, &mut nodes, &mut Vec::new()).map_err(in_input)?;
+    validate(&compacted, &nodes).map_err(|error| in_input(error.into()))?;
+    if let Err(error) = str::from_utf8(&compacted) {
+        let error = Error::new(ErrorKind::InvalidUtf8, error.valid_up_to());
+        return Err(in_input(error));
+    }
+    let root = match ValueRef::root(&compacted, &nodes) {
+        Some(root) => root,
+        None => return Err(Error::new(ErrorKind::UnexpectedEnd, input.len())),
+    };
+    let start = output.len();
+    let result = write_canonical(root, output, scheme);
+    if result.is_err() {
+        output.truncate(start);
+    }
+    result.map_err(in_input)
+}
+
+/// Check the bytes that compacting will remove. Compacting drops every byte
+/// up to and including a space outside strings, which would turn `[1 2]`
+/// into `[12]` and `[tr ue]` into `[true]`. So only allow JSON whitespace,
+/// and not in the middle of a number or literal.
+fn check_whitespace(input: &[u8]) -> Result<(), Error> {
+    let mut in_string = false;
+    let mut escaped = false;
+    // Whether the last byte that wasn't whitespace was part of a number or
+    // a literal, and whether there has been whitespace since.
+    let mut in_scalar = false;
+    let mut after_whitespace = false;
+    for (offset, &byte) in input.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        let scalar = match byte {
+            b' ' | b'\t' | b'\n' | b'\r' => {
+                after_whitespace = true;
+                continue;
+            },
+            0..=0x1F => return Err(Error::new(ErrorKind::InvalidValue, offset)),
+            b'"' => {
+                in_string = true;
+                false
+            },
+            b'{' | b'}' | b'[' | b']' | b',' | b':' => false,
+            _ => true,
+        };
+        if scalar && in_scalar && after_whitespace {
+            return Err(Error::new(ErrorKind::InvalidValue, offset));
+        }
+        in_scalar = scalar;
+        after_whitespace = false;
+    }
+    Ok(())
+}
+
+
+/// An object or array which we are part way through writing.
+enum Frame<'a> {
+    Object(vec::IntoIter<(ValueRef<'a>, ValueRef<'a>)>, bool),
+    Array(ArrayIter<'a>, bool),
+}
+
+/// Write validated, compacted JSON in canonical form. This doesn't recurse
+/// so deeply nested JSON can't overflow the stack.
+fn write_canonical<S: Scheme>(root: ValueRef, output: &mut Vec<u8>, scheme: &S) -> Result<(), Error> {
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut next = Some(root);
+    loop {
+        if let Some(value) = next.take() {
+            match value.kind() {
+                JsonKind::Object => {
+                    let members = sorted_members(value, scheme)?;
+                    output.push(b'{');
+                    stack.push(Frame::Object(members.into_iter(), true));
+                },
+                JsonKind::Array => {
+                    output.push(b'[');
+                    stack.push(Frame::Array(value.array_iter().unwrap(), true));
+                },
+                JsonKind::String => write_string(value.raw_bytes(), output),
+                JsonKind::Number => scheme.write_number(value, output)?,
+                JsonKind::Bool | JsonKind::Null => {
+                    output.extend_from_slice(value.raw_bytes());
+                },
+            }
+        }
+        // Find the next value to write, closing any finished containers.
+        match stack.last_mut() {
+            None => return Ok(()),
+            Some(&mut Frame::Object(ref mut members, ref mut first)) => {
+                match members.next() {
+                    Some((key, value)) => {
+                        if !*first {
+                            output.push(b',');
+                        }
+                        *first = false;
+                        write_string(key.raw_bytes(), output);
+                        output.push(b':');
+                        next = Some(value);
+                        continue;
+                    },
+                    None => output.push(b'}'),
+                }
+            },
+            Some(&mut Frame::Array(ref mut elements, ref mut first)) => {
+                match elements.next() {
+                    Some(value) => {
+                        if !*first {
+                            output.push(b',');
+                        }
+                        *first = false;
+                        next = Some(value);
+                        continue;
+                    },
+                    None => output.push(b']'),
+                }
+            },
+        }
+        stack.pop();
+    }
+}
+
+/// The keys and values of an object sorted by key. Fails if two keys are
+/// the same once decoded.
+fn sorted_members<'a, S: Scheme>(object: ValueRef<'a>, scheme: &S) -> Result<Vec<(ValueRef<'a>, ValueRef<'a>)>, Error> {
+    let compare_keys = |a: ValueRef, b: ValueRef| {
+        scheme.compare_keys(a.escaped_str().unwrap(), b.escaped_str().unwrap())
+    };
+    let mut members: Vec<_> = object.object_iter().unwrap().collect();
+    members.sort_by(|a, b| compare_keys(a.0, b.0));
+    for pair in members.windows(2) {
+        if compare_keys(pair[0].0, pair[1].0) == Ordering::Equal {
+            return Err(Error::new(ErrorKind::DuplicateKey, pair[1].0.offset()));
+        }
+    }
+    Ok(members)
+}
+
+/// Write a compacted string. Compacting has already removed every escape
+/// that isn't needed, but writes the hex digits of `\u` escapes in upper case
+/// where canonical JSON uses lower case.
+fn write_string(raw: &[u8], output: &mut Vec<u8>) {
+    let mut iter = raw.iter();
+    while let Some(&byte) = iter.next() {
+        output.push(byte);
+        if byte != b'\\' {
+            continue;
+        }
+        if let Some(&escaped) = iter.next() {
+            output.push(escaped);
+            if escaped == b'u' {
+                output.extend(iter.by_ref().take(4).map(u8::to_ascii_lowercase));
+            }
+        }
+    }
+}
+
+/// Write an integer as it is. The number has been validated so it doesn't
+/// have any leading zeros.
+fn write_integer(value: ValueRef, output: &mut Vec<u8>) -> Result<(), Error> {
+    let error = |kind| Err(Error::new(kind, value.offset()));
+    let bytes = value.raw_bytes();
+    if bytes == b"-0" {
+        return error(ErrorKind::NotAnInteger);
+    }
+    match parse_i64(bytes) {
+        Ok(integer) if integer.unsigned_abs() <= MAX_SAFE_INTEGER as u64 => {},
+        Ok(_) | Err(NumberError::Overflow) => return error(ErrorKind::IntegerOutOfRange),
+        Err(NumberError::NotAnInteger) => return error(ErrorKind::NotAnInteger),
+        Err(NumberError::Invalid) => return error(ErrorKind::InvalidNumber),
+    }
+    output.extend_from_slice(bytes);
+    Ok(())
+}
+
+/// Write a number the way ECMAScript's `Number.prototype.toString` does.
+/// This uses the shortest digits that convert back to the same double, which
+/// is what Rust's formatting produces too, but places the decimal point and
+/// writes the exponent differently.
+fn write_es6_number(number: f64, output: &mut Vec<u8>) {
+    if number == 0.0 {
+        // This includes -0.
+        output.push(b'0');
+        return;
+    }
+    if number < 0.0 {
+        output.push(b'-');
+    }
+    let (mut digits, exponent) = scientific(&format!("{:e}", number.abs()));
+    round_half_even(&mut digits, exponent, number.abs());
+    // The number is 0.digits * 10^point.
+    let point = exponent + 1;
+    let length = digits.len() as i32;
+    if length <= point && point <= 21 {
+        output.extend_from_slice(&digits);
+        output.extend((length..point).map(|_| b'0'));
+    } else if 0 < point && point <= 21 {
+        output.extend_from_slice(&digits[..point as usize]);
+        output.push(b'.');
+        output.extend_from_slice(&digits[point as usize..]);
+    } else if -6 < point && point <= 0 {
+        output.extend_from_slice(b"0.");
+        output.extend((point..0).map(|_| b'0'));
+        output.extend_from_slice(&digits);
+    } else {
+        output.push(digits[0]);
+        if digits.len() > 1 {
+            output.push(b'.');
+            output.extend_from_slice(&digits[1..]);
+        }
+        let sign = if exponent < 0 { '-' } else { '+' };
+        output.extend_from_slice(format!("e{}{}", sign, exponent.abs()).as_bytes());
+    }
+}
+
+/// Split a number formatted with `{:e}` into its digits and exponent.
+fn scientific(formatted: &str) -> (Vec<u8>, i32) {
+    let (mantissa, exponent) = formatted.split_at(formatted.find('e').unwrap());
+    let digits = mantissa.bytes().filter(|c| *c != b'.').collect();
+    (digits, exponent[1..].parse().unwrap())
+}
+
+/// When a number is exactly halfway between the two closest shortest digit
+/// sequences that convert back to it, ECMAScript picks the even one but Rust
+/// may pick the odd one. For example `1424953923781206.25` should be written
+/// as `1424953923781206.2`.
+fn round_half_even(digits: &mut [u8], exponent: i32, number: f64) {
+    let length = digits.len();
+    let last = digits[length - 1];
+    if (last - b'0') % 2 == 0 {
+        return;
+    }
+    // Cheaply check if the next digit is a 5 before finding the exact
+    // decimal value, which is at most 767 significant digits.
+    let (next, _) = scientific(&format!("{:.*e}", length, number));
+    if next[length] != b'5' {
+        return;
+    }
+    let (exact, exact_exponent) = scientific(&format!("{:.*e}", 800, number));
+    if exact_exponent != exponent || exact[length + 1..].iter().any(|c| *c != b'0') {
+        return;
+    }
+    let even = if exact[..length] == digits[..] {
+        if last == b'9' {
+            return;
+        }
+        last + 1
+    } else {
+        last - 1
+    };
+    digits[length - 1] = even;
+    let candidate = format!(
+        "{}e{}", str::from_utf8(digits).unwrap(), exponent - length as i32 + 1
+    );
+    if candidate.parse::<f64>() != Ok(number) {
+        digits[length - 1] = last;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{canonicalize, canonicalize_jcs, write_es6_number};
+    use error::ErrorKind;
+
+    fn canonical(input: &str) -> String {
+        let mut output = Vec::new();
+        canonicalize(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn canonical_error(input: &[u8]) -> (ErrorKind, usize) {
+        let mut output = b"[".to_vec();
+        let error = canonicalize(input, &mut output).unwrap_err();
+        assert_eq!(b"[", &output[..]);
+        (error.kind(), error.offset())
+    }
+
+    #[test]
+    fn canonical_matrix_examples() {
+        assert_eq!("{}", canonical("{}"));
+        assert_eq!(
+            r#"{"one":1,"two":"Two"}"#,
+            canonical(r#"{"one": 1, "two": "Two"}"#)
+        );
+        assert_eq!(r#"{"a":"1","b":"2"}"#, canonical(r#"{"b": "2", "a": "1"}"#));
+        assert_eq!(
+            r#"{"auth":{"mxid":"@john.doe:example.com","profile":{"display_name":"John Doe","three_pids":[{"address":"john.doe@example.org","medium":"email"},{"address":"123456789","medium":"msisdn"}]},"success":true}}"#,
+            canonical(r#"{
+                "auth": {
+                    "success": true,
+                    "mxid": "@john.doe:example.com",
+                    "profile": {
+                        "display_name": "John Doe",
+                        "three_pids": [
+                            {"medium": "email", "address": "john.doe@example.org"},
+                            {"medium": "msisdn", "address": "123456789"}
+                        ]
+                    }
+                }
+            }"#)
+        );
+        assert_eq!(r#"{"a":"日本語"}"#, canonical(r#"{"a": "日本語"}"#));
+        assert_eq!(r#"{"日":1,"本":2}"#, canonical(r#"{"本": 2, "日": 1}"#));
+        assert_eq!(r#"{"a":"日"}"#, canonical(r#"{"a": "\u65E5"}"#));
+        assert_eq!(r#"{"a":null}"#, canonical(r#"{"a": null}"#));
+    }
+
+    #[test]
+    fn canonical_strings() {
+        assert_eq!(
+            r#"["\u001f\n\"\\/é"]"#,
+            canonical(r#"["\u001F\u000a\"\\\/é"]"#)
+        );
+        // Keys are sorted by their decoded contents.
+        assert_eq!(
+            r#"{"A":1,"a":2,"é":3,"｡":5,"😀":4}"#,
+            canonical(r#"{"｡":5,"😀":4,"é":3,"a":2,"A":1}"#)
+        );
+    }
+
+    #[test]
+    fn canonical_nested() {
+        assert_eq!(
+            r#"[{"a":[],"b":{}},[[1,{"c":true,"d":false}]]]"#,
+            canonical(r#"[{"b":{},"a":[]},[[1,{"d":false,"c":true}]]]"#)
+        );
+        let deep = "[".repeat(10000) + &"]".repeat(10000);
+        assert_eq!(deep, canonical(&deep));
+    }
+
+    #[test]
+    fn canonical_numbers() {
+        assert_eq!(
+            "[9007199254740991,-9007199254740991,0,-1]",
+            canonical("[9007199254740991,-9007199254740991,0,-1]")
+        );
+        assert_eq!((ErrorKind::NotAnInteger, 4), canonical_error(b"[1, 1.5]"));
+        assert_eq!((ErrorKind::NotAnInteger, 1), canonical_error(b"[1.0]"));
+        assert_eq!((ErrorKind::NotAnInteger, 1), canonical_error(b"[1e10]"));
+        assert_eq!((ErrorKind::NotAnInteger, 1), canonical_error(b"[1E2]"));
+        assert_eq!((ErrorKind::NotAnInteger, 1), canonical_error(b"[-0]"));
+        assert_eq!((ErrorKind::IntegerOutOfRange, 1), canonical_error(b"[9007199254740992]"));
+        assert_eq!((ErrorKind::IntegerOutOfRange, 1), canonical_error(b"[-9007199254740992]"));
+        assert_eq!(
+            (ErrorKind::IntegerOutOfRange, 1),
+            canonical_error(b"[-170141183460469231731687303715884105728]")
+        );
+        assert_eq!(
+            (ErrorKind::IntegerOutOfRange, 1),
+            canonical_error(b"[170141183460469231731687303715884105727]")
+        );
+    }
+
+    #[test]
+    fn canonical_errors() {
+        assert_eq!(
+            (ErrorKind::DuplicateKey, 9),
+            canonical_error(br#"{"a": 1, "a": 2}"#)
+        );
+        assert_eq!((ErrorKind::InvalidLiteral, 4), canonical_error(b"[\n  tru]"));
+        assert_eq!((ErrorKind::UnterminatedString, 1), canonical_error(b"[\"a"));
+        assert_eq!((ErrorKind::InvalidUtf8, 5), canonical_error(b"[ \"ab\xFF\"]"));
+        // Whitespace can't split a number or literal, and other control
+        // characters aren't whitespace.
+        assert_eq!((ErrorKind::InvalidValue, 3), canonical_error(b"[1 2]"));
+        assert_eq!((ErrorKind::InvalidValue, 4), canonical_error(b"[tr ue]"));
+        assert_eq!((ErrorKind::InvalidValue, 2), canonical_error(b"[1\x01]"));
+        assert_eq!((ErrorKind::InvalidValue, 4), canonical_error(b"[\"a\"\x00]"));
+        assert_eq!("[1,\"a b\\\" c\"]", canonical("[ 1 ,\t\"a b\\\" c\" ]\r\n"));
+    }
+
+    fn jcs(input: &str) -> String {
+        let mut output = Vec::new();
+        canonicalize_jcs(input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn es6_number(bits: u64) -> String {
+        let mut output = Vec::new();
+        write_es6_number(f64::from_bits(bits), &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn jcs_rfc_example() {
+        assert_eq!(
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#,
+            jcs(r#"{
+                "numbers": [333333333.33333329, 1E30, 4.50,
+                            2e-3, 0.000000000000000000000000001],
+                "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+                "literals": [null, true, false]
+            }"#)
+        );
+    }
+
+    #[test]
+    fn jcs_rfc_sorting() {
+        assert_eq!(
+            "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",\
+             \"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\
+             \"\u{20ac}\":\"Euro Sign\",\"\u{1f600}\":\"Emoji: Grinning Face\",\
+             \"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}",
+            jcs(r#"{
+                "\u20ac": "Euro Sign",
+                "\r": "Carriage Return",
+                "\ufb33": "Hebrew Letter Dalet With Dagesh",
+                "1": "One",
+                "\ud83d\ude00": "Emoji: Grinning Face",
+                "\u0080": "Control",
+                "\u00f6": "Latin Small Letter O With Diaeresis"
+            }"#)
+        );
+    }
+
+    #[test]
+    fn jcs_rfc_numbers() {
+        let examples = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x8000000000000001, "-5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0xffefffffffffffff, "-1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0xc340000000000000, "-9007199254740992"),
+            (0x4430000000000000, "295147905179352830000"),
+            (0x44b52d02c7e14af5, "9.999999999999997e+22"),
+            (0x44b52d02c7e14af6, "1e+23"),
+            (0x44b52d02c7e14af7, "1.0000000000000001e+23"),
+            (0x444b1ae4d6e2ef4e, "999999999999999700000"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x41b3de4355555553, "333333333.3333332"),
+            (0x41b3de4355555554, "333333333.33333325"),
+            (0x41b3de4355555555, "333333333.3333333"),
+            (0x41b3de4355555556, "333333333.3333334"),
+            (0x41b3de4355555557, "333333333.33333343"),
+            (0xbecbf647612f3696, "-0.0000033333333333333333"),
+            (0x43143ff3c1cb0959, "1424953923781206.2"),
+        ];
+        for &(bits, expected) in examples.iter() {
+            assert_eq!(expected, es6_number(bits));
+        }
+    }
+
+    #[test]
+    fn jcs_errors() {
+        let mut output = Vec::new();
+        let error = canonicalize_jcs(b"[1e400]", &mut output).unwrap_err();
+        assert_eq!((ErrorKind::NumberOutOfRange, 1), (error.kind(), error.offset()));
+        let error = canonicalize_jcs(br#"{"\u00e9":1,"\u00E9":2}"#, &mut output).unwrap_err();
+        assert_eq!((ErrorKind::DuplicateKey, 12), (error.kind(), error.offset()));
+        let error = canonicalize_jcs(b"[1 2]", &mut output).unwrap_err();
+        assert_eq!((ErrorKind::InvalidValue, 3), (error.kind(), error.offset()));
+        assert!(output.is_empty());
+    }
+}
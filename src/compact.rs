@@ -12,55 +12,293 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
+use error::{Error, ErrorKind};
 use readhex::*;
+use std::slice;
+use std::str;
 
 const HEX : [u8 ; 16] = *b"0123456789ABCDEF";
 
 /// Reduce the JSON encoded as UTF-8 to its shortest form by removing whitespace
 /// and removing unnecessary string escapes. Succeeds if it writes the new JSON
-/// to the output vector. Fails if the input contained an unterminated string.
-pub fn compact(input: &[u8], output: &mut Vec<u8>) -> Result<(),()> {
-    if compact_(input, output) {
+/// to the output vector. Fails if the input contained an unterminated string
+/// or an invalid `\u` escape, the offset of the error is the offset in the
+/// input.
+pub fn compact(input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    compact_::<_, Utf8>(input, output, &mut ())
+}
+
+/// Compact the JSON like `compact` but only write 7-bit ASCII. Characters
+/// outside ASCII are written as `\uXXXX` escapes, using a surrogate pair for
+/// characters outside the Basic Multilingual Plane. This applies to both
+/// UTF-8 and `\u` escapes in the input. Also fails if the strings in the
+/// input aren't valid UTF-8, or if there are non-ASCII bytes outside of
+/// strings.
+pub fn compact_ascii(input: &[u8], output: &mut Vec<u8>) -> Result<(), Error> {
+    compact_::<_, Ascii>(input, output, &mut ())
+}
+
+/// Compact the JSON like `compact` and record in `map` where each byte of
+/// the output came from in the input. The map is cleared first. Offsets in
+/// errors from parsing or validating the output can then be translated back
+/// to offsets, lines and columns in the input.
+///
+/// The map uses offsets from the start of the JSON written by this call, so
+/// the output vector should usually be empty.
+pub fn compact_with_map(input: &[u8], output: &mut Vec<u8>, map: &mut OffsetMap) -> Result<(), Error> {
+    map.clear();
+    compact_::<_, Utf8>(input, output, map)
+}
+
+/// Maps offsets in compacted JSON back to offsets in the original input.
+///
+/// The original offset of a compacted byte is its offset plus the number of
+/// bytes removed before it, less any bytes added by rewriting escapes. The
+/// map stores that difference as runs, adding a new run each time the
+/// difference changes, so the map stays small for typical pretty-printed
+/// JSON. It also records where each line of the input starts.
+#[derive(Clone, Debug, Default)]
+pub struct OffsetMap {
+    runs: Vec<Run>,
+    newlines: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Run {
+    compacted: u32,
+    delta: i64,
+}
+
+impl OffsetMap {
+    /// Create an empty map.
+    pub fn new() -> OffsetMap {
+        OffsetMap::default()
+    }
+
+    /// Remove the contents of the map, keeping the allocated buffers.
+    pub fn clear(&mut self) {
+        self.runs.clear();
+        self.newlines.clear();
+    }
+
+    /// The offset in the original input of the byte at `compacted` in the
+    /// compacted output. An offset at the end of the output maps to just after
+    /// the last byte of JSON in the input.
+    pub fn original_offset(&self, compacted: usize) -> usize {
+        let index = self.runs.partition_point(
+            |run| run.compacted as usize <= compacted
+        );
+        match index {
+            0 => compacted,
+            _ => (compacted as i64 + self.runs[index - 1].delta) as usize,
+        }
+    }
+
+    /// The 1-based line and column of an offset in the original input.
+    /// Columns count bytes.
+    pub fn line_column(&self, original: usize) -> (usize, usize) {
+        let line = self.newlines.partition_point(
+            |newline| (*newline as usize) < original
+        );
+        let line_start = match line {
+            0 => 0,
+            _ => self.newlines[line - 1] as usize + 1,
+        };
+        (line + 1, original - line_start + 1)
+    }
+
+    /// The offset in the original input where a 1-based line starts.
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        match line {
+            0 => None,
+            1 => Some(0),
+            _ => self.newlines.get(line - 2).map(|newline| *newline as usize + 1),
+        }
+    }
+
+    fn delta(&self) -> i64 {
+        self.runs.last().map_or(0, |run| run.delta)
+    }
+}
+
+/// Follows the progress of `compact_` so that `OffsetMap` can be filled out
+/// without slowing down plain `compact`.
+trait Tracker {
+    /// The next byte of output comes from `original` in the input.
+    fn mark(&mut self, compacted: usize, original: usize);
+    /// There is a new line after `original` in the input.
+    fn newline(&mut self, original: usize);
+}
+
+impl Tracker for () {
+    #[inline(always)]
+    fn mark(&mut self, _compacted: usize, _original: usize) {}
+    #[inline(always)]
+    fn newline(&mut self, _original: usize) {}
+}
+
+impl Tracker for OffsetMap {
+    fn mark(&mut self, compacted: usize, original: usize) {
+        let delta = original as i64 - compacted as i64;
+        if delta != self.delta() {
+            self.runs.push(Run { compacted: compacted as u32, delta });
+        }
+    }
+
+    fn newline(&mut self, original: usize) {
+        self.newlines.push(original as u32);
+    }
+}
+
+/// How `compact_` writes characters outside ASCII.
+trait Encoding {
+    /// Copy a character that starts with the non-ASCII byte `first` at
+    /// `start` in the input. The rest of the character is read from `iter`.
+    fn copy(iter: &mut slice::Iter<u8>, first: u8, output: &mut Vec<u8>, start: usize) -> Result<(), Error>;
+    /// Write a character outside ASCII that came from a `\u` escape.
+    fn write(codepoint: u32, output: &mut Vec<u8>);
+    /// Check a non-ASCII byte at `start` in the input that isn't in a string.
+    /// This isn't valid JSON, but compacting leaves it to `validate` to say so
+    /// unless the output has to be ASCII.
+    fn check_outside_string(start: usize) -> Result<(), Error>;
+}
+
+/// Writes characters as UTF-8, copying UTF-8 in the input as it is.
+struct Utf8;
+
+/// Writes characters as `\u` escapes.
+struct Ascii;
+
+impl Encoding for Utf8 {
+    #[inline(always)]
+    fn copy(_iter: &mut slice::Iter<u8>, first: u8, output: &mut Vec<u8>, _start: usize) -> Result<(), Error> {
+        output.push(first);
+        Ok(())
+    }
+
+    fn write(codepoint: u32, output: &mut Vec<u8>) {
+        if codepoint < 0x800 {
+            output.push((codepoint >> 6) as u8 | 0xC0);
+            output.push((codepoint as u8 & 0x3F) | 0x80);
+        } else if codepoint < 0x10000 {
+            output.push((codepoint >> 12) as u8 | 0xE0);
+            output.push(((codepoint >> 6) & 0x3F) as u8 | 0x80);
+            output.push((codepoint as u8 & 0x3F) | 0x80);
+        } else {
+            output.push((codepoint >> 18) as u8 | 0xF0);
+            output.push(((codepoint >> 12) & 0x3F) as u8 | 0x80);
+            output.push(((codepoint >> 6) & 0x3F) as u8 | 0x80);
+            output.push((codepoint as u8 & 0x3F) | 0x80);
+        }
+    }
+
+    #[inline(always)]
+    fn check_outside_string(_start: usize) -> Result<(), Error> {
         Ok(())
-    } else {
-        Err(())
     }
 }
 
-fn compact_(input: &[u8], output: &mut Vec<u8>) -> bool {
+impl Encoding for Ascii {
+    fn copy(iter: &mut slice::Iter<u8>, first: u8, output: &mut Vec<u8>, start: usize) -> Result<(), Error> {
+        let length = match first {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Err(Error::new(ErrorKind::InvalidUtf8, start)),
+        };
+        let rest = iter.as_slice();
+        if rest.len() < length - 1 {
+            return Err(Error::new(ErrorKind::InvalidUtf8, start));
+        }
+        let mut bytes = [first, 0, 0, 0];
+        bytes[1..length].copy_from_slice(&rest[..length - 1]);
+        let c = match str::from_utf8(&bytes[..length]) {
+            Ok(decoded) => decoded.chars().next().unwrap(),
+            Err(_) => return Err(Error::new(ErrorKind::InvalidUtf8, start)),
+        };
+        *iter = rest[length - 1..].iter();
+        Ascii::write(c as u32, output);
+        Ok(())
+    }
+
+    fn write(codepoint: u32, output: &mut Vec<u8>) {
+        if codepoint < 0x10000 {
+            write_unicode_escape(codepoint, output);
+        } else {
+            let codepoint = codepoint - 0x10000;
+            write_unicode_escape(0xD800 | (codepoint >> 10), output);
+            write_unicode_escape(0xDC00 | (codepoint & 0x3FF), output);
+        }
+    }
+
+    fn check_outside_string(start: usize) -> Result<(), Error> {
+        Err(Error::new(ErrorKind::InvalidValue, start))
+    }
+}
+
+fn write_unicode_escape(escaped: u32, output: &mut Vec<u8>) {
+    output.push(b'\\');
+    output.push(b'u');
+    output.push(HEX[(escaped >> 12) as usize & 0xF]);
+    output.push(HEX[(escaped >> 8) as usize & 0xF]);
+    output.push(HEX[(escaped >> 4) as usize & 0xF]);
+    output.push(HEX[escaped as usize & 0xF]);
+}
+
+fn compact_<T: Tracker, E: Encoding>(input: &[u8], output: &mut Vec<u8>, tracker: &mut T) -> Result<(), Error> {
     let mut iter = input.iter();
+    let output_start = output.len();
 
     loop {
         let input_char = match iter.next() {
-            None => return true,
+            None => return Ok(()),
             Some(value) => *value,
         };
         if input_char <= b' ' { // Whitespace '\n', '\r', '\t', ' '
+            if input_char == b'\n' {
+                tracker.newline(input.len() - iter.len() - 1);
+            }
             continue;
         }
+        tracker.mark(output.len() - output_start, input.len() - iter.len() - 1);
+        if input_char >= 0x80 {
+            E::check_outside_string(input.len() - iter.len() - 1)?;
+        }
         output.push(input_char);
         if input_char == b'\"' { // Double Quote '\"'
+            let string_start = input.len() - iter.len() - 1;
             loop {
                 let input_char = match iter.next() {
-                    None => return false,
+                    None => return Err(Error::new(
+                        ErrorKind::UnterminatedString, string_start
+                    )),
                     Some(value) => *value,
                 };
                 if input_char == b'\\' { // Back Slash '\\'
+                    let escape_start = input.len() - iter.len() - 1;
                     let input_char = match iter.next() {
-                        None => return false,
+                        None => return Err(Error::new(
+                            ErrorKind::TruncatedEscape, escape_start
+                        )),
                         Some(value) => *value,
                     };
+                    tracker.mark(output.len() - output_start, escape_start);
                     if input_char == b'u' { // Unicode escape "u"
-                        if !compact_unicode_escape(&mut iter, output) {
-                            return false
-                        }
+                        compact_unicode_escape::<E>(&mut iter, output, escape_start)?;
                     } else if input_char == b'/' { // Forward Slash '/'
                         output.push(input_char);
                     } else {
                         output.push(b'\\');
                         output.push(input_char);
                     }
+                    tracker.mark(output.len() - output_start, input.len() - iter.len());
+                } else if input_char >= 0x80 {
+                    let start = input.len() - iter.len() - 1;
+                    E::copy(&mut iter, input_char, output, start)?;
                 } else {
+                    if input_char == b'\n' {
+                        tracker.newline(input.len() - iter.len() - 1);
+                    }
                     output.push(input_char);
                 }
                 if input_char == b'\"' {
@@ -71,24 +309,21 @@ fn compact_(input: &[u8], output: &mut Vec<u8>) -> bool {
     }
 }
 
-fn compact_unicode_escape<'a, T: Iterator<Item=&'a u8>>(iter: &mut T, output: &mut Vec<u8>) -> bool {
-    let h0 = match iter.next() {
-        None => return false,
-        Some(value) => *value
-    };
-    let h1 = match iter.next() {
-        None => return false,
-        Some(value) => *value
+/// Read the 4 hex digits of a `\u` escape that started at `escape_start`.
+fn read_unicode_escape(iter: &mut slice::Iter<u8>, escape_start: usize) -> Result<u32, Error> {
+    let (h0, h1, h2, h3) = match iter.as_slice() {
+        &[h0, h1, h2, h3, ..] => (h0, h1, h2, h3),
+        _ => return Err(Error::new(ErrorKind::TruncatedEscape, escape_start)),
     };
-    let h2 = match iter.next() {
-        None => return false,
-        Some(value) => *value
-    };
-    let h3 = match iter.next() {
-        None => return false,
-        Some(value) => *value
-    };
-    let escaped = read_hexdigits(h0, h1, h2, h3);
+    if !is_hexdigits(h0, h1, h2, h3) {
+        return Err(Error::new(ErrorKind::InvalidHex, escape_start));
+    }
+    *iter = iter.as_slice()[4..].iter();
+    Ok(read_hexdigits(h0, h1, h2, h3))
+}
+
+fn compact_unicode_escape<E: Encoding>(iter: &mut slice::Iter<u8>, output: &mut Vec<u8>, escape_start: usize) -> Result<(), Error> {
+    let escaped = read_unicode_escape(iter, escape_start)?;
     if escaped < 0x20 {
         output.push(b'\\');
         match escaped {
@@ -110,53 +345,32 @@ fn compact_unicode_escape<'a, T: Iterator<Item=&'a u8>>(iter: &mut T, output: &m
             output.push(b'\\');
         }
         output.push(escaped as u8);
-    } else if escaped < 0x800 {
-        output.push((escaped >> 6) as u8 | 0xC0);
-        output.push((escaped as u8 & 0x3F) | 0x80);
-    } else if escaped < 0xD800 || escaped >= 0xE000 {
-        output.push((escaped >> 12) as u8 | 0xE0);
-        output.push(((escaped >> 6) & 0x3F) as u8 | 0x80);
-        output.push((escaped as u8 & 0x3F) | 0x80);
+    } else if !(0xD800..0xE000).contains(&escaped) {
+        E::write(escaped, output);
     } else {
-        // surrogate pair
-        if iter.next() == None {
-            return false
+        // surrogate pair, a high surrogate must be followed by a low one.
+        if escaped >= 0xDC00 || !iter.as_slice().starts_with(b"\\u") {
+            return Err(Error::new(ErrorKind::LoneSurrogate, escape_start));
         }
-        if iter.next() == None {
-            return false
+        iter.nth(1);
+        let surrogate = read_unicode_escape(iter, escape_start + 6)?;
+        if !(0xDC00..0xE000).contains(&surrogate) {
+            return Err(Error::new(ErrorKind::LoneSurrogate, escape_start));
         }
-        let h0 = match iter.next() {
-            None => return false,
-            Some(value) => *value
-        };
-        let h1 = match iter.next() {
-            None => return false,
-            Some(value) => *value
-        };
-        let h2 = match iter.next() {
-            None => return false,
-            Some(value) => *value
-        };
-        let h3 = match iter.next() {
-            None => return false,
-            Some(value) => *value
-        };
-        let surrogate = read_hexdigits(h0, h1, h2, h3);
         let codepoint = 0x10000 + (
             ((escaped & 0x3FF) << 10) | (surrogate & 0x3FF)
         );
-        output.push((codepoint >> 18) as u8 | 0xF0);
-        output.push(((codepoint >> 12) & 0x3F) as u8 | 0x80);
-        output.push(((codepoint >> 6) & 0x3F) as u8 | 0x80);
-        output.push((codepoint as u8 & 0x3F) | 0x80);
+        E::write(codepoint, output);
     }
-    true
+    Ok(())
 }
 
 
-
 #[cfg(test)]
 mod tests {
+    use super::{compact_with_map, OffsetMap};
+    use error::ErrorKind;
+
     fn compact(input_json: &str) -> String {
         let mut output : Vec<u8> = Vec::with_capacity(input_json.as_bytes().len());
         super::compact(input_json.as_bytes(), &mut output).unwrap();
@@ -209,4 +423,83 @@ mod tests {
         assert_eq!("[\"\u{20820}\"]", compact("[\"\\uD842\\uDC20\"]"));
         assert_eq!("[\"\u{10FFFF}\"]", compact("[\"\\uDBFF\\uDFFF\"]"));
     }
+
+    fn compact_error(input_json: &str) -> (ErrorKind, usize) {
+        let mut output = Vec::new();
+        let error = super::compact(input_json.as_bytes(), &mut output).unwrap_err();
+        (error.kind(), error.offset())
+    }
+
+    #[test]
+    fn compact_errors() {
+        assert_eq!((ErrorKind::UnterminatedString, 4), compact_error(r#"[1, "abc]"#));
+        assert_eq!((ErrorKind::TruncatedEscape, 3), compact_error(r#"[ "\"#));
+        assert_eq!((ErrorKind::TruncatedEscape, 2), compact_error(r#"["\u00"#));
+        assert_eq!((ErrorKind::InvalidHex, 2), compact_error(r#"["\u00G0"]"#));
+        assert_eq!((ErrorKind::LoneSurrogate, 2), compact_error(r#"["\uDC20"]"#));
+        assert_eq!((ErrorKind::LoneSurrogate, 2), compact_error(r#"["\uD842x"]"#));
+        assert_eq!((ErrorKind::LoneSurrogate, 2), compact_error(r#"["\uD842\u0041"]"#));
+        assert_eq!((ErrorKind::InvalidHex, 8), compact_error(r#"["\uD842\uDCxx"]"#));
+    }
+
+    fn compact_ascii(input: &[u8]) -> Result<String, (ErrorKind, usize)> {
+        let mut output = Vec::new();
+        match super::compact_ascii(input, &mut output) {
+            Ok(()) => Ok(String::from_utf8(output).unwrap()),
+            Err(error) => Err((error.kind(), error.offset())),
+        }
+    }
+
+    #[test]
+    fn compact_ascii_escapes() {
+        // Spell the backslashes of escapes as '|'.
+        let escaped = |json: &str| json.replace('|', "\\");
+        assert_eq!(
+            Ok(escaped(r#"{"|u00E9":["a/b|u0120|u1820|uFFFF","|uD842|uDC20|uDBFF|uDFFF"]}"#)),
+            compact_ascii(
+                "{ \"\u{e9}\" : [ \"a\\/b\\u0120\u{1820}\\uffff\", \"\u{20820}\\uDBFF\\uDFFF\" ] }".as_bytes()
+            )
+        );
+        assert_eq!(
+            Ok(escaped(r#"["|"|||n|u0001a"]"#)),
+            compact_ascii(escaped(r#"["|u0022|u005C|u000A|u0001|u0061"]"#).as_bytes())
+        );
+    }
+
+    #[test]
+    fn compact_ascii_errors() {
+        assert_eq!(Err((ErrorKind::InvalidUtf8, 3)), compact_ascii(b"[\"a\xE9\"]"));
+        assert_eq!(Err((ErrorKind::InvalidUtf8, 2)), compact_ascii(b"[\"\x80\"]"));
+        assert_eq!(Err((ErrorKind::InvalidUtf8, 2)), compact_ascii(b"[\"\xED\xA0\x80\"]"));
+        assert_eq!(Err((ErrorKind::InvalidUtf8, 2)), compact_ascii(b"[\"\xF0\x9F"));
+        assert_eq!(Err((ErrorKind::LoneSurrogate, 2)), compact_ascii(br#"["\uDC20"]"#));
+        assert_eq!(Err((ErrorKind::InvalidValue, 3)), compact_ascii(b"[1,\xC3\xA9]"));
+    }
+
+    #[test]
+    fn compact_offset_map() {
+        let input = b"{\n  \"a\" : [1,\n    \"\\u00e9\\/x\"],\n  \"b\": true\n}\n";
+        let mut output = Vec::new();
+        let mut map = OffsetMap::new();
+        compact_with_map(input, &mut output, &mut map).unwrap();
+        assert_eq!(&b"{\"a\":[1,\"\xC3\xA9/x\"],\"b\":true}"[..], &output[..]);
+        for (compacted, original) in output.iter().enumerate().filter(
+            |&(_, byte)| byte.is_ascii() && *byte != b'/'
+        ).map(|(compacted, _)| (compacted, map.original_offset(compacted))) {
+            assert_eq!(output[compacted], input[original]);
+        }
+        // The 'é' came from the "\u00e9" escape and the '/' from "\/".
+        assert_eq!(19, map.original_offset(9));
+        assert_eq!(25, map.original_offset(11));
+        assert_eq!(input.len() - 1, map.original_offset(output.len()));
+        assert_eq!((1, 1), map.line_column(0));
+        assert_eq!((2, 3), map.line_column(map.original_offset(1)));
+        assert_eq!((4, 8), map.line_column(map.original_offset(20)));
+        assert_eq!(Some(14), map.line_start(3));
+        assert_eq!(None, map.line_start(0));
+
+        compact_with_map(b"[1, 2]", &mut output, &mut map).unwrap();
+        assert_eq!(1, map.original_offset(1));
+        assert_eq!(4, map.original_offset(3));
+    }
 }
@@ -0,0 +1,341 @@
+use compact::OffsetMap;
+use error::{Error, ErrorKind};
+use std::cmp;
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+use validate::ValidationError;
+
+
+/// A problem found in some JSON, for reporting to a user. Can be made from
+/// any of the errors returned when compacting, parsing or validating, or
+/// collected from every problem in the JSON with `validate::validate_all`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Diagnostic {
+    kind: ErrorKind,
+    range: Range<usize>,
+    node: Option<usize>,
+}
+
+/// How to render a `Diagnostic`.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// Use ANSI escape codes to colour the output for a terminal.
+    pub color: bool,
+    /// Lines longer than this many characters are cut down to this many
+    /// characters around the problem.
+    pub max_line_width: usize,
+}
+
+impl Default for RenderOptions {
+    fn default() -> RenderOptions {
+        RenderOptions { color: false, max_line_width: 120 }
+    }
+}
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+const ELLIPSIS: &str = "...";
+
+impl Diagnostic {
+    /// Create a diagnostic for a problem in the bytes in `range`, found at
+    /// `node` if the JSON had been parsed.
+    pub fn new(kind: ErrorKind, range: Range<usize>, node: Option<usize>) -> Diagnostic {
+        Diagnostic { kind, range, node }
+    }
+
+    /// What was wrong.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The range of bytes that were wrong. This is empty if the error only
+    /// had an offset.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// The index of the node where the problem was found, if the problem was
+    /// found after parsing.
+    pub fn node(&self) -> Option<usize> {
+        self.node
+    }
+
+    /// Translate the range of a problem found in compacted JSON to the range
+    /// in the original input, using the map recorded by
+    /// `compact::compact_with_map`. Errors from `parse` and `validate` need
+    /// translating before they can be rendered, errors from `compact` don't.
+    pub fn to_original(&self, map: &OffsetMap) -> Diagnostic {
+        let start = map.original_offset(self.range.start);
+        let end = if self.range.end > self.range.start {
+            map.original_offset(self.range.end - 1) + 1
+        } else {
+            start
+        };
+        Diagnostic::new(self.kind, start..end, self.node)
+    }
+
+    /// Describe the problem for a person reading the original input. Shows
+    /// the line and column, the line itself, and marks the bytes that were
+    /// wrong with carets:
+    ///
+    /// ```text
+    /// error: invalid literal
+    ///  --> line 2, column 8
+    ///   |
+    /// 2 |   "a": tru,
+    ///   |        ^^^
+    /// ```
+    ///
+    /// The range must already be translated with `to_original`. The line is
+    /// found with the `map` recorded when compacting `original_input` if
+    /// there is one, otherwise by scanning the input for new lines. A map
+    /// recorded from different input is ignored if it doesn't agree with
+    /// `original_input`. Columns count characters.
+    pub fn render(&self, original_input: &[u8], map: Option<&OffsetMap>, options: &RenderOptions) -> String {
+        let start = cmp::min(self.range.start, original_input.len());
+        let (line_number, line_start) = match map.and_then(|map| map_line(map, original_input, start)) {
+            Some(line) => line,
+            None => scan_line(original_input, start),
+        };
+        let line_end = original_input[start..].iter()
+            .position(|byte| *byte == b'\n')
+            .map_or(original_input.len(), |newline| start + newline);
+        let mut line = &original_input[line_start..line_end];
+        if line.last() == Some(&b'\r') {
+            line = &line[..line.len() - 1];
+        }
+        let end = cmp::max(start, cmp::min(self.range.end, line_start + line.len()));
+        let text: Vec<char> = String::from_utf8_lossy(line).chars().collect();
+        let column = chars(&original_input[line_start..start]);
+        let marked = cmp::max(1, chars(&original_input[start..end]));
+
+        // Cut long lines down to a window around the problem.
+        let width = cmp::max(options.max_line_width, 1);
+        let (first, last) = if text.len() <= width {
+            (0, text.len())
+        } else {
+            let first = cmp::min(column.saturating_sub(width / 2), text.len() - width);
+            (first, first + width)
+        };
+        let column_in_window = cmp::min(column, last) - first;
+        let marked = cmp::max(1, cmp::min(marked, last.saturating_sub(column)));
+
+        let (red, blue, bold, reset) = if options.color {
+            (RED, BLUE, BOLD, RESET)
+        } else {
+            ("", "", "", "")
+        };
+        let number = line_number.to_string();
+        let gutter = " ".repeat(number.len());
+        let mut output = String::new();
+        let _ = writeln!(output, "{}error{}{}: {}{}", red, reset, bold, self.kind, reset);
+        let _ = writeln!(
+            output, "{}{}-->{} line {}, column {}",
+            gutter, blue, reset, line_number, column + 1
+        );
+        let _ = writeln!(output, "{}{} |{}", blue, gutter, reset);
+        let _ = write!(output, "{}{} |{} ", blue, number, reset);
+        let mut padding = String::new();
+        if first > 0 {
+            output.push_str(ELLIPSIS);
+            padding.push_str("   ");
+        }
+        output.extend(&text[first..last]);
+        if last < text.len() {
+            output.push_str(ELLIPSIS);
+        }
+        output.push('\n');
+        // Copy tabs so that the carets line up with the line above.
+        padding.extend(text[first..first + column_in_window].iter().map(
+            |c| if *c == '\t' { '\t' } else { ' ' }
+        ));
+        let _ = writeln!(
+            output, "{}{} |{} {}{}{}{}",
+            blue, gutter, reset, padding, red, "^".repeat(marked), reset
+        );
+        output
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.range.start)
+    }
+}
+
+impl From<Error> for Diagnostic {
+    fn from(error: Error) -> Diagnostic {
+        Diagnostic::new(error.kind(), error.offset()..error.offset(), None)
+    }
+}
+
+impl From<ValidationError> for Diagnostic {
+    fn from(error: ValidationError) -> Diagnostic {
+        Diagnostic::new(error.kind(), error.range(), Some(error.node()))
+    }
+}
+
+/// The 1-based line number and start of the line containing `offset`, as
+/// recorded in `map`. Returns `None` if the map doesn't match the input.
+fn map_line(map: &OffsetMap, input: &[u8], offset: usize) -> Option<(usize, usize)> {
+    let (line_number, _) = map.line_column(offset);
+    let line_start = map.line_start(line_number)?;
+    if line_start > offset
+        || (line_start > 0 && input[line_start - 1] != b'\n')
+        || input[line_start..offset].contains(&b'\n')
+    {
+        return None;
+    }
+    Some((line_number, line_start))
+}
+
+/// The 1-based line number and start of the line containing `offset`, found
+/// by counting the new lines before it.
+fn scan_line(input: &[u8], offset: usize) -> (usize, usize) {
+    let before = &input[..offset];
+    let line_number = before.iter().filter(|byte| **byte == b'\n').count() + 1;
+    let line_start = before.iter()
+        .rposition(|byte| *byte == b'\n')
+        .map_or(0, |newline| newline + 1);
+    (line_number, line_start)
+}
+
+/// The number of characters in some UTF-8, counting invalid bytes as one
+/// character each.
+fn chars(bytes: &[u8]) -> usize {
+    String::from_utf8_lossy(bytes).chars().count()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, RenderOptions};
+    use compact::{compact_with_map, OffsetMap};
+    use error::ErrorKind;
+    use parse::parse;
+    use validate::{validate, validate_all};
+
+    const INPUT: &[u8] = b"{\n  \"a\": [1, 2],\n  \"b\": tru,\n\t\"c\": \"\xC3\xA9\\g\"\n}\n";
+
+    fn diagnostics(input: &[u8]) -> (Vec<Diagnostic>, OffsetMap) {
+        let mut compacted = Vec::new();
+        let mut map = OffsetMap::new();
+        compact_with_map(input, &mut compacted, &mut map).unwrap();
+        let mut nodes = Vec::new();
+        let mut stack = Vec::new();
+        parse(&compacted, &mut nodes, &mut stack).unwrap();
+        let found = validate_all(&compacted, &nodes).iter().map(
+            |diagnostic| diagnostic.to_original(&map)
+        ).collect();
+        (found, map)
+    }
+
+    #[test]
+    fn diagnostic_to_original() {
+        let (found, _) = diagnostics(INPUT);
+        assert_eq!(2, found.len());
+        assert_eq!(ErrorKind::InvalidLiteral, found[0].kind());
+        assert_eq!(b"tru", &INPUT[found[0].range()]);
+        assert_eq!(Some(6), found[0].node());
+        assert_eq!(ErrorKind::InvalidEscape, found[1].kind());
+        assert_eq!(b"\\g", &INPUT[found[1].range()]);
+    }
+
+    #[test]
+    fn diagnostic_render() {
+        let (found, map) = diagnostics(INPUT);
+        let options = RenderOptions::default();
+        assert_eq!(
+            "error: invalid literal\n \
+             --> line 3, column 8\n  \
+             |\n\
+             3 |   \"b\": tru,\n  \
+             |        ^^^\n",
+            found[0].render(INPUT, Some(&map), &options)
+        );
+        // Tabs are copied and the 'é' is one column.
+        assert_eq!(
+            "error: invalid escape\n \
+             --> line 4, column 9\n  \
+             |\n\
+             4 | \t\"c\": \"\u{e9}\\g\"\n  \
+             | \t       ^^\n",
+            found[1].render(INPUT, Some(&map), &options)
+        );
+    }
+
+    #[test]
+    fn diagnostic_render_without_map() {
+        let (found, map) = diagnostics(INPUT);
+        let options = RenderOptions::default();
+        let expected = found[0].render(INPUT, Some(&map), &options);
+        assert_eq!(expected, found[0].render(INPUT, None, &options));
+        // A map from other input or an empty map is ignored.
+        let (_, other) = diagnostics(b"[\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n1]");
+        assert_eq!(expected, found[0].render(INPUT, Some(&other), &options));
+        assert_eq!(expected, found[0].render(INPUT, Some(&OffsetMap::new()), &options));
+        assert_eq!(
+            found[1].render(INPUT, None, &options),
+            found[1].render(INPUT, Some(&other), &options)
+        );
+    }
+
+    #[test]
+    fn diagnostic_render_error() {
+        let input = b"[1,\n \"abc]";
+        let mut compacted = Vec::new();
+        let mut map = OffsetMap::new();
+        let error = compact_with_map(input, &mut compacted, &mut map).unwrap_err();
+        let rendered = Diagnostic::from(error).render(input, Some(&map), &RenderOptions::default());
+        assert_eq!(
+            "error: unterminated string\n \
+             --> line 2, column 2\n  \
+             |\n\
+             2 |  \"abc]\n  \
+             |  ^\n",
+            rendered
+        );
+    }
+
+    #[test]
+    fn diagnostic_render_long_line() {
+        let mut input = b"[".to_vec();
+        for _ in 0..100 {
+            input.extend_from_slice(b"1,");
+        }
+        input.extend_from_slice(b"nul");
+        for _ in 0..100 {
+            input.extend_from_slice(b",1");
+        }
+        input.push(b']');
+        let (found, map) = diagnostics(&input);
+        let options = RenderOptions { color: false, max_line_width: 21 };
+        assert_eq!(
+            "error: invalid literal\n \
+             --> line 1, column 202\n  \
+             |\n\
+             1 | ...1,1,1,1,1,nul,1,1,1,1...\n  \
+             |              ^^^\n",
+            found[0].render(&input, Some(&map), &options)
+        );
+    }
+
+    #[test]
+    fn diagnostic_render_color() {
+        let input = b"[1,x]";
+        let mut compacted = Vec::new();
+        let mut map = OffsetMap::new();
+        compact_with_map(input, &mut compacted, &mut map).unwrap();
+        let mut nodes = Vec::new();
+        parse(&compacted, &mut nodes, &mut Vec::new()).unwrap();
+        let error = validate(&compacted, &nodes).unwrap_err();
+        let options = RenderOptions { color: true, ..RenderOptions::default() };
+        let diagnostic = Diagnostic::from(error).to_original(&map);
+        let rendered = diagnostic.render(input, Some(&map), &options);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: invalid value\x1b[0m\n"));
+        assert!(rendered.ends_with("\x1b[1;31m^\x1b[0m\n"));
+    }
+}
@@ -0,0 +1,169 @@
+use compact::compact;
+use error::Error;
+use parents::ParentMap;
+use parse::{parse, Node, Stack};
+use pointer::JsonPointer;
+use validate::validate;
+use value::ValueRef;
+
+
+/// A parsed JSON document. Owns the compacted JSON bytes and the array of
+/// nodes produced by parsing them.
+///
+/// The buffers are kept when a new document is loaded into an existing
+/// `Document`, so an application parsing many documents in a loop can reuse
+/// a single `Document` and avoid allocating once the buffers have grown large
+/// enough.
+pub struct Document {
+    bytes: Vec<u8>,
+    nodes: Vec<Node>,
+    stack: Vec<Stack>,
+}
+
+impl Document {
+    /// Create an empty document with no root.
+    pub fn new() -> Document {
+        Document {
+            bytes: Vec::new(),
+            nodes: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    /// Compact and parse the JSON in the input.
+    pub fn from_slice(input: &[u8]) -> Result<Document, Error> {
+        let mut document = Document::new();
+        document.load(input)?;
+        Ok(document)
+    }
+
+    /// Compact, parse and validate the JSON in the input.
+    /// See `validate::validate` for why validation might be needed.
+    pub fn from_slice_validated(input: &[u8]) -> Result<Document, Error> {
+        let mut document = Document::new();
+        document.load_validated(input)?;
+        Ok(document)
+    }
+
+    /// Replace the contents of this document by compacting and parsing the
+    /// JSON in the input. The existing buffers are reused. If this fails then
+    /// the document is left empty.
+    pub fn load(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.clear();
+        let result = self.load_(input);
+        if result.is_err() {
+            self.clear();
+        }
+        result
+    }
+
+    /// Replace the contents of this document by compacting, parsing and
+    /// validating the JSON in the input. The existing buffers are reused.
+    /// If this fails then the document is left empty.
+    pub fn load_validated(&mut self, input: &[u8]) -> Result<(), Error> {
+        self.clear();
+        let result = self.load_(input).and_then(
+            |()| validate(&self.bytes, &self.nodes).map_err(Error::from)
+        );
+        if result.is_err() {
+            self.clear();
+        }
+        result
+    }
+
+    fn load_(&mut self, input: &[u8]) -> Result<(), Error> {
+        compact(input, &mut self.bytes)?;
+        self.stack.clear();
+        parse(&self.bytes, &mut self.nodes, &mut self.stack)
+    }
+
+    /// Remove the contents of the document, keeping the allocated buffers.
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+        self.nodes.clear();
+        self.stack.clear();
+    }
+
+    /// The compacted JSON bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// The nodes produced by parsing the compacted JSON.
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// The root object or array, or `None` if the document is empty.
+    pub fn root(&self) -> Option<ValueRef<'_>> {
+        ValueRef::root(&self.bytes, &self.nodes)
+    }
+
+    /// Build a JSON Pointer to the node at `index`, for example to report
+    /// where in the document a problem was found. This computes the parents
+    /// of every node on each call, use a `ParentMap` directly to look up
+    /// many paths.
+    pub fn path_of(&self, index: usize) -> Option<JsonPointer> {
+        ParentMap::new(&self.bytes, &self.nodes).path_of(index)
+    }
+
+    /// Look up a value using an RFC 6901 JSON Pointer like `"/a/b/0"`.
+    /// Returns `None` if the pointer doesn't refer to anything in the
+    /// document. See `pointer::resolve`.
+    pub fn pointer(&self, pointer: &str) -> Option<ValueRef<'_>> {
+        self.root()?.pointer(pointer)
+    }
+}
+
+impl Default for Document {
+    fn default() -> Document {
+        Document::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::Document;
+    use error::ErrorKind;
+    use parse::Node;
+
+    #[test]
+    fn document_from_slice() {
+        let document = Document::from_slice(br#" { "a" : [ 1 , 2 ] } "#).unwrap();
+        assert_eq!(br#"{"a":[1,2]}"#, document.bytes());
+        let root = document.root().unwrap();
+        assert_eq!(&Node {children: 4, length_in_bytes: 11}, root.node());
+        assert_eq!(5, document.nodes().len());
+    }
+
+    #[test]
+    fn document_from_slice_invalid() {
+        let error = Document::from_slice(br#"{"a":"#).err().unwrap();
+        assert_eq!(ErrorKind::UnexpectedEnd, error.kind());
+        assert!(Document::from_slice(b"[01]").is_ok());
+        assert!(Document::from_slice_validated(b"[01]").is_err());
+        assert!(Document::from_slice_validated(b"[1]").is_ok());
+    }
+
+    #[test]
+    fn document_reuse() {
+        let mut document = Document::new();
+        assert!(document.root().is_none());
+        document.load(b"[[1],[2,3]]").unwrap();
+        assert_eq!(6, document.nodes().len());
+        document.load(b"{}").unwrap();
+        assert_eq!(b"{}", document.bytes());
+        assert_eq!(1, document.nodes().len());
+        assert!(document.load_validated(b"[tru]").is_err());
+        assert!(document.root().is_none());
+        assert!(document.bytes().is_empty());
+    }
+
+    #[test]
+    fn document_path_of() {
+        let document = Document::from_slice(br#"{"content":{"body":"hi"}}"#).unwrap();
+        assert_eq!("/content/body", document.path_of(4).unwrap().to_string());
+        assert!(document.path_of(5).is_none());
+    }
+}
@@ -0,0 +1,143 @@
+use std::error;
+use std::fmt;
+
+
+/// The kind of problem found in the JSON.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// A string was missing its closing `"`.
+    UnterminatedString,
+    /// The input ended in the middle of a `\` escape.
+    TruncatedEscape,
+    /// A `\u` escape wasn't followed by 4 hex digits.
+    InvalidHex,
+    /// A `\u` escape for a UTF-16 surrogate wasn't part of a valid pair.
+    LoneSurrogate,
+    /// The input ended before the end of the root object or array.
+    UnexpectedEnd,
+    /// Expected the start of an object or array.
+    ExpectedContainer,
+    /// An object or array was closed with the wrong bracket.
+    MismatchedBracket,
+    /// An object key wasn't followed by a `:`.
+    ExpectedColon,
+    /// An object key wasn't a string.
+    InvalidKey,
+    /// A value was empty or started with a character that can't start a
+    /// JSON value.
+    InvalidValue,
+    /// A value looked like `true`, `false` or `null` but wasn't.
+    InvalidLiteral,
+    /// A number didn't follow the JSON number syntax.
+    InvalidNumber,
+    /// A number had a `0` followed by more digits before the decimal point.
+    LeadingZero,
+    /// A `\` in a string was followed by a character that isn't a JSON
+    /// escape.
+    InvalidEscape,
+    /// A string contained an escape that compacting should have removed.
+    UncompactedEscape,
+    /// A string contained a control character that should have been escaped.
+    ControlCharacter,
+    /// There was more input after the end of the root object or array.
+    TrailingData,
+    /// The JSON wasn't valid UTF-8.
+    InvalidUtf8,
+    /// An object had the same key more than once.
+    DuplicateKey,
+    /// A number wasn't a whole number.
+    NotAnInteger,
+    /// An integer was too large to be represented exactly.
+    IntegerOutOfRange,
+    /// A number was too large to be represented as a double.
+    NumberOutOfRange,
+}
+
+impl ErrorKind {
+    /// A short description of the problem.
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ErrorKind::UnterminatedString => "unterminated string",
+            ErrorKind::TruncatedEscape => "truncated escape",
+            ErrorKind::InvalidHex => "invalid hex digits in unicode escape",
+            ErrorKind::LoneSurrogate => "unpaired surrogate in unicode escape",
+            ErrorKind::UnexpectedEnd => "unexpected end of input",
+            ErrorKind::ExpectedContainer => "expected '{' or '['",
+            ErrorKind::MismatchedBracket => "mismatched bracket",
+            ErrorKind::ExpectedColon => "expected ':' after object key",
+            ErrorKind::InvalidKey => "object key is not a string",
+            ErrorKind::InvalidValue => "invalid value",
+            ErrorKind::InvalidLiteral => "invalid literal",
+            ErrorKind::InvalidNumber => "invalid number",
+            ErrorKind::LeadingZero => "leading zero in number",
+            ErrorKind::InvalidEscape => "invalid escape",
+            ErrorKind::UncompactedEscape => "escape should have been removed by compacting",
+            ErrorKind::ControlCharacter => "unescaped control character in string",
+            ErrorKind::TrailingData => "trailing data after JSON",
+            ErrorKind::InvalidUtf8 => "invalid UTF-8",
+            ErrorKind::DuplicateKey => "duplicate key in object",
+            ErrorKind::NotAnInteger => "number is not an integer",
+            ErrorKind::IntegerOutOfRange => "integer is out of range",
+            ErrorKind::NumberOutOfRange => "number is out of range",
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
+
+
+/// An error from compacting, parsing or validating JSON. Records what went
+/// wrong and the byte offset where the problem was found.
+///
+/// Errors from `compact::compact` have offsets in the original input. Errors
+/// from `parse::parse` have offsets in the compacted JSON they were given.
+/// `validate::validate` returns a more detailed `ValidationError` which can be
+/// converted into an `Error`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    offset: usize,
+}
+
+impl Error {
+    /// Create an error of the given kind at `offset` bytes into the input.
+    pub fn new(kind: ErrorKind, offset: usize) -> Error {
+        Error { kind, offset }
+    }
+
+    /// What went wrong.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The byte offset where the problem was found.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.offset)
+    }
+}
+
+impl error::Error for Error {}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_display() {
+        let error = Error::new(ErrorKind::MismatchedBracket, 12);
+        assert_eq!(ErrorKind::MismatchedBracket, error.kind());
+        assert_eq!(12, error.offset());
+        assert_eq!("mismatched bracket at offset 12", error.to_string());
+    }
+}
@@ -16,11 +16,23 @@
 #![cfg_attr(all(test, feature = "quickcheck_test"), feature(plugin))]
 #![cfg_attr(all(test, feature = "quickcheck_test"), plugin(quickcheck_macros))]
 
+pub mod array_index;
+pub mod canonical;
 pub mod compact;
+pub mod diagnostic;
+pub mod document;
+pub mod error;
 pub mod readhex;
+pub mod number;
+pub mod parents;
 pub mod parse;
+pub mod pointer;
+pub mod pretty;
+pub mod query;
 pub mod validate;
 pub mod strings;
+pub mod value;
+pub mod visit;
 
 #[cfg(all(test, feature = "quickcheck_test"))]
 extern crate quickcheck;
@@ -0,0 +1,571 @@
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::hash;
+use std::str;
+use validate::is_number;
+
+
+/// The reason a JSON number couldn't be converted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NumberError {
+    /// The bytes weren't a valid JSON number.
+    Invalid,
+    /// The number had a fraction or an exponent so isn't an integer.
+    NotAnInteger,
+    /// The number was too large, or too negative, for the type.
+    Overflow,
+}
+
+impl fmt::Display for NumberError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            NumberError::Invalid => "invalid number",
+            NumberError::NotAnInteger => "number is not an integer",
+            NumberError::Overflow => "number out of range",
+        })
+    }
+}
+
+impl error::Error for NumberError {}
+
+
+/// The parts of a JSON number. The number is
+/// `[-] integer [. fraction] [e exponent]`.
+struct Parts<'a> {
+    negative: bool,
+    integer: &'a [u8],
+    fraction: &'a [u8],
+    exponent: &'a [u8],
+}
+
+/// Split a JSON number into its parts, checking the syntax with
+/// `validate::is_number`. The exponent includes its sign.
+fn split(bytes: &[u8]) -> Option<Parts<'_>> {
+    if !is_number(bytes) {
+        return None;
+    }
+    let (negative, rest) = match bytes.first() {
+        Some(&b'-') => (true, &bytes[1..]),
+        _ => (false, bytes),
+    };
+    let integer_len = rest.iter().take_while(|c| c.is_ascii_digit()).count();
+    let (integer, rest) = rest.split_at(integer_len);
+    let fraction_len = rest.iter().take_while(|c| **c != b'e' && **c != b'E').count();
+    let (fraction, exponent) = rest.split_at(fraction_len);
+    // Drop the '.' and the 'e' or 'E'.
+    let fraction = fraction.get(1..).unwrap_or(b"");
+    let exponent = exponent.get(1..).unwrap_or(b"");
+    Some(Parts { negative, integer, fraction, exponent })
+}
+
+/// Read the digits of an integer with no fraction or exponent.
+fn integer_magnitude(bytes: &[u8]) -> Result<(bool, u64), NumberError> {
+    let parts = split(bytes).ok_or(NumberError::Invalid)?;
+    if !parts.fraction.is_empty() || !parts.exponent.is_empty() {
+        return Err(NumberError::NotAnInteger);
+    }
+    let mut magnitude: u64 = 0;
+    for c in parts.integer {
+        magnitude = magnitude.checked_mul(10)
+            .and_then(|value| value.checked_add((c - b'0') as u64))
+            .ok_or(NumberError::Overflow)?;
+    }
+    Ok((parts.negative, magnitude))
+}
+
+/// Convert a JSON number to an `i64`. Fails if the number has a fraction or
+/// an exponent, even if its value is a whole number.
+pub fn parse_i64(bytes: &[u8]) -> Result<i64, NumberError> {
+    let (negative, magnitude) = integer_magnitude(bytes)?;
+    if negative {
+        if magnitude > i64::MAX as u64 + 1 {
+            return Err(NumberError::Overflow);
+        }
+        Ok((magnitude as i64).wrapping_neg())
+    } else {
+        if magnitude > i64::MAX as u64 {
+            return Err(NumberError::Overflow);
+        }
+        Ok(magnitude as i64)
+    }
+}
+
+/// Convert a JSON number to a `u64`. Fails if the number has a fraction or
+/// an exponent, even if its value is a whole number. `-0` is allowed.
+pub fn parse_u64(bytes: &[u8]) -> Result<u64, NumberError> {
+    let (negative, magnitude) = integer_magnitude(bytes)?;
+    if negative && magnitude != 0 {
+        return Err(NumberError::Overflow);
+    }
+    Ok(magnitude)
+}
+
+/// Powers of ten which can be represented exactly as an `f64`.
+const POW10: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+    1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// Convert a JSON number to the nearest `f64`. Numbers too small to
+/// represent become zero. Fails if the number is too large to represent.
+///
+/// Numbers with at most 15 significant digits and a small exponent are
+/// converted exactly with a single multiplication or division. Other
+/// numbers fall back to the standard library parser, which is correctly
+/// rounded for any number of digits and any exponent.
+pub fn parse_f64(bytes: &[u8]) -> Result<f64, NumberError> {
+    let parts = split(bytes).ok_or(NumberError::Invalid)?;
+    if let Some(value) = fast_f64(&parts) {
+        return Ok(value);
+    }
+    // The syntax has been checked so the bytes are ASCII.
+    let value: f64 = str::from_utf8(bytes).ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or(NumberError::Invalid)?;
+    if value.is_infinite() {
+        return Err(NumberError::Overflow);
+    }
+    Ok(value)
+}
+
+/// Convert numbers whose significant digits and power of ten are both
+/// exactly representable. Since the inputs are exact and IEEE arithmetic is
+/// correctly rounded the result is correctly rounded.
+fn fast_f64(parts: &Parts) -> Option<f64> {
+    // Don't bother with exponents with too many digits to fit.
+    if parts.exponent.len() > 4 {
+        return None;
+    }
+    let mut mantissa: u64 = 0;
+    let mut digits = 0;
+    for c in parts.integer.iter().chain(parts.fraction) {
+        if mantissa == 0 && *c == b'0' {
+            // Leading zeros aren't significant.
+            continue;
+        }
+        digits += 1;
+        if digits > 15 {
+            return None;
+        }
+        mantissa = mantissa * 10 + (c - b'0') as u64;
+    }
+    let mut exponent: i64 = 0;
+    if !parts.exponent.is_empty() {
+        exponent = str::from_utf8(parts.exponent).ok()?.parse().ok()?;
+    }
+    let exponent = exponent - parts.fraction.len() as i64;
+    let mut value = mantissa as f64;
+    if mantissa != 0 {
+        if (0..=22).contains(&exponent) {
+            value *= POW10[exponent as usize];
+        } else if (-22..0).contains(&exponent) {
+            value /= POW10[-exponent as usize];
+        } else {
+            return None;
+        }
+    }
+    Some(if parts.negative { -value } else { value })
+}
+
+
+/// A JSON number held exactly as a sign, a string of decimal digits and a
+/// power of ten. The value of the number is `digits * 10^exponent`, negated
+/// if the sign is negative. Nothing is rounded, so numbers with more
+/// precision than an `f64` can be relayed or compared without losing any
+/// digits.
+///
+/// Numbers compare equal if they have the same value, so `1.50`, `15e-1`
+/// and `1.5` are all equal, as are `0` and `-0`.
+#[derive(Clone, Debug)]
+pub struct JsonNumber {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl JsonNumber {
+    /// Read a JSON number. Leading zeros are removed from the digits but
+    /// trailing zeros are kept until the number is normalised. Fails if the
+    /// bytes aren't a valid JSON number or if the exponent doesn't fit in an
+    /// `i64`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<JsonNumber, NumberError> {
+        let parts = split(bytes).ok_or(NumberError::Invalid)?;
+        let mut exponent: i64 = 0;
+        if !parts.exponent.is_empty() {
+            exponent = str::from_utf8(parts.exponent).ok()
+                .and_then(|text| text.parse().ok())
+                .ok_or(NumberError::Overflow)?;
+        }
+        let exponent = exponent.checked_sub(parts.fraction.len() as i64)
+            .ok_or(NumberError::Overflow)?;
+        let mut digits = String::with_capacity(parts.integer.len() + parts.fraction.len());
+        for c in parts.integer.iter().chain(parts.fraction) {
+            if digits.is_empty() && *c == b'0' {
+                continue;
+            }
+            digits.push(*c as char);
+        }
+        Ok(JsonNumber { negative: parts.negative, digits, exponent })
+    }
+
+    /// Whether the number was written with a minus sign.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The significant digits of the number. Empty if the number is zero.
+    pub fn digits(&self) -> &str {
+        &self.digits
+    }
+
+    /// The power of ten the digits are multiplied by.
+    pub fn exponent(&self) -> i64 {
+        self.exponent
+    }
+
+    /// Whether the value of the number is zero.
+    pub fn is_zero(&self) -> bool {
+        self.digits.is_empty()
+    }
+
+    /// Remove trailing zeros from the digits, and the sign from zero, so that
+    /// numbers with the same value have the same representation.
+    pub fn normalize(&mut self) {
+        let (negative, digits, exponent) = self.normalized_parts();
+        let len = digits.len();
+        self.negative = negative;
+        self.exponent = exponent;
+        self.digits.truncate(len);
+    }
+
+    /// The sign, digits and exponent with trailing zeros removed from the
+    /// digits. Zero is always positive with an exponent of zero.
+    fn normalized_parts(&self) -> (bool, &str, i64) {
+        let digits = self.digits.trim_end_matches('0');
+        if digits.is_empty() {
+            return (false, "", 0);
+        }
+        let zeros = (self.digits.len() - digits.len()) as i64;
+        (self.negative, digits, self.exponent.saturating_add(zeros))
+    }
+
+    /// Whether the value of the number is a whole number.
+    pub fn is_integer(&self) -> bool {
+        self.normalized_parts().2 >= 0
+    }
+
+    /// Write the number in plain decimal notation without an exponent, for
+    /// example `-1234.5`. Fractions have a leading `0` before the decimal
+    /// point. Beware that a number like `1e1000000` becomes a million
+    /// digits long, check the `exponent` first if the input is untrusted or
+    /// use `to_string` which keeps the exponent.
+    pub fn to_decimal_string(&self) -> String {
+        let (negative, digits, exponent) = self.normalized_parts();
+        if digits.is_empty() {
+            return "0".to_string();
+        }
+        let mut output = String::new();
+        if negative {
+            output.push('-');
+        }
+        if exponent >= 0 {
+            output.push_str(digits);
+            output.extend((0..exponent).map(|_| '0'));
+        } else {
+            let point = digits.len() as i64 + exponent;
+            if point > 0 {
+                output.push_str(&digits[..point as usize]);
+                output.push('.');
+                output.push_str(&digits[point as usize..]);
+            } else {
+                output.push_str("0.");
+                output.extend((0..-point).map(|_| '0'));
+                output.push_str(digits);
+            }
+        }
+        output
+    }
+
+    /// Write the number as an integer of any size, for example to pass to a
+    /// big integer library. Returns `None` if the number isn't a whole
+    /// number.
+    pub fn to_integer_string(&self) -> Option<String> {
+        if !self.is_integer() {
+            return None;
+        }
+        Some(self.to_decimal_string())
+    }
+
+    /// Convert the number to an `i128` if it is a whole number in range.
+    pub fn to_i128(&self) -> Result<i128, NumberError> {
+        let (negative, digits, exponent) = self.normalized_parts();
+        if exponent < 0 {
+            return Err(NumberError::NotAnInteger);
+        }
+        // Accumulate as a negative number since that has the larger range.
+        let mut value: i128 = 0;
+        let zeros = (0..exponent).map(|_| &b'0');
+        for c in digits.as_bytes().iter().chain(zeros) {
+            value = value.checked_mul(10)
+                .and_then(|value| value.checked_sub((c - b'0') as i128))
+                .ok_or(NumberError::Overflow)?;
+        }
+        if negative {
+            Ok(value)
+        } else {
+            value.checked_neg().ok_or(NumberError::Overflow)
+        }
+    }
+
+    /// Convert the number to the nearest `f64`. See `parse_f64`.
+    pub fn to_f64(&self) -> Result<f64, NumberError> {
+        let (negative, digits, exponent) = self.normalized_parts();
+        if digits.is_empty() {
+            return Ok(if self.negative { -0.0 } else { 0.0 });
+        }
+        let text = format!("{}{}e{}", if negative { "-" } else { "" }, digits, exponent);
+        parse_f64(text.as_bytes())
+    }
+}
+
+/// Writes the normalised digits followed by the exponent, for example
+/// `-12345e-1`, leaving out the exponent if it is zero. This is a valid JSON
+/// number and is never much longer than the input, unlike
+/// `to_decimal_string`.
+impl fmt::Display for JsonNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (negative, digits, exponent) = self.normalized_parts();
+        if digits.is_empty() {
+            return f.write_str("0");
+        }
+        if negative {
+            f.write_str("-")?;
+        }
+        f.write_str(digits)?;
+        if exponent != 0 {
+            write!(f, "e{}", exponent)?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialEq for JsonNumber {
+    fn eq(&self, other: &JsonNumber) -> bool {
+        self.normalized_parts() == other.normalized_parts()
+    }
+}
+
+impl Eq for JsonNumber {}
+
+impl hash::Hash for JsonNumber {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.normalized_parts().hash(state);
+    }
+}
+
+impl PartialOrd for JsonNumber {
+    fn partial_cmp(&self, other: &JsonNumber) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JsonNumber {
+    fn cmp(&self, other: &JsonNumber) -> Ordering {
+        let (a_negative, a_digits, a_exponent) = self.normalized_parts();
+        let (b_negative, b_digits, b_exponent) = other.normalized_parts();
+        let sign = |negative: bool, digits: &str| {
+            if digits.is_empty() { 0 } else if negative { -1 } else { 1 }
+        };
+        let a_sign = sign(a_negative, a_digits);
+        let b_sign = sign(b_negative, b_digits);
+        if a_sign != b_sign || a_sign == 0 {
+            return a_sign.cmp(&b_sign);
+        }
+        // Compare the position of the most significant digit, then the
+        // digits themselves. Neither has trailing zeros so if one is a prefix
+        // of the other then the longer one is larger.
+        let a_magnitude = a_digits.len() as i128 + a_exponent as i128;
+        let b_magnitude = b_digits.len() as i128 + b_exponent as i128;
+        let ordering = a_magnitude.cmp(&b_magnitude)
+            .then_with(|| a_digits.cmp(b_digits));
+        if a_sign < 0 { ordering.reverse() } else { ordering }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_i64() {
+        assert_eq!(Ok(0), parse_i64(b"0"));
+        assert_eq!(Ok(0), parse_i64(b"-0"));
+        assert_eq!(Ok(123), parse_i64(b"123"));
+        assert_eq!(Ok(-123), parse_i64(b"-123"));
+        assert_eq!(Ok(i64::MAX), parse_i64(b"9223372036854775807"));
+        assert_eq!(Ok(i64::MIN), parse_i64(b"-9223372036854775808"));
+        assert_eq!(Err(NumberError::Overflow), parse_i64(b"9223372036854775808"));
+        assert_eq!(Err(NumberError::Overflow), parse_i64(b"-9223372036854775809"));
+        assert_eq!(Err(NumberError::Overflow), parse_i64(b"100000000000000000000000"));
+        assert_eq!(Err(NumberError::NotAnInteger), parse_i64(b"1.0"));
+        assert_eq!(Err(NumberError::NotAnInteger), parse_i64(b"1e2"));
+        assert_eq!(Err(NumberError::Invalid), parse_i64(b"01"));
+        assert_eq!(Err(NumberError::Invalid), parse_i64(b""));
+        assert_eq!(Err(NumberError::Invalid), parse_i64(b"-"));
+        assert_eq!(Err(NumberError::Invalid), parse_i64(b"true"));
+    }
+
+    #[test]
+    fn number_u64() {
+        assert_eq!(Ok(0), parse_u64(b"-0"));
+        assert_eq!(Ok(u64::MAX), parse_u64(b"18446744073709551615"));
+        assert_eq!(Err(NumberError::Overflow), parse_u64(b"18446744073709551616"));
+        assert_eq!(Err(NumberError::Overflow), parse_u64(b"-1"));
+        assert_eq!(Err(NumberError::NotAnInteger), parse_u64(b"0.5"));
+    }
+
+    #[test]
+    fn number_f64() {
+        assert_eq!(Ok(0.0), parse_f64(b"0"));
+        assert!(parse_f64(b"-0").unwrap().is_sign_negative());
+        assert_eq!(Ok(1.5), parse_f64(b"1.5"));
+        assert_eq!(Ok(-1.0e7), parse_f64(b"-1.0e7"));
+        assert_eq!(Ok(1e-7), parse_f64(b"1E-7"));
+        assert_eq!(Ok(0.1), parse_f64(b"0.1"));
+        assert_eq!(Ok(123456.789e3), parse_f64(b"123456.789e+3"));
+        assert_eq!(Ok(9007199254740992.0), parse_f64(b"9007199254740993"));
+        assert_eq!(Ok(0.0), parse_f64(b"1e-400"));
+        assert_eq!(Ok(f64::MAX), parse_f64(b"1.7976931348623157e308"));
+        assert_eq!(Ok(5e-324), parse_f64(b"4.9406564584124654e-324"));
+        assert_eq!(Ok(2.225073858507201e-308), parse_f64(b"2.2250738585072011e-308"));
+        assert_eq!(Err(NumberError::Overflow), parse_f64(b"1e400"));
+        assert_eq!(Err(NumberError::Overflow), parse_f64(b"-1e99999999999999999999"));
+        assert_eq!(Ok(0.0), parse_f64(b"0e99999999999999999999"));
+        assert_eq!(Err(NumberError::Invalid), parse_f64(b"1."));
+        assert_eq!(Err(NumberError::Invalid), parse_f64(b".5"));
+        assert_eq!(Err(NumberError::Invalid), parse_f64(b"1e"));
+        assert_eq!(Err(NumberError::Invalid), parse_f64(b"+1"));
+        assert_eq!(Err(NumberError::Invalid), parse_f64(b"NaN"));
+    }
+
+    #[test]
+    fn number_f64_long_mantissa() {
+        assert_eq!(
+            Ok(0.1),
+            parse_f64(b"0.1000000000000000055511151231257827021181583404541015625")
+        );
+        // Exactly halfway between two floats, rounds to even.
+        assert_eq!(Ok(9007199254740992.0), parse_f64(b"9007199254740993.0000"));
+        // Just above halfway, rounds up.
+        assert_eq!(
+            Ok(9007199254740994.0),
+            parse_f64(b"9007199254740993.00000000000000000000000000001")
+        );
+        assert_eq!(
+            Ok(1.0),
+            parse_f64(b"1.00000000000000011102230246251565404236316680908203125")
+        );
+        assert_eq!(
+            Ok(1.0000000000000002),
+            parse_f64(b"1.00000000000000011102230246251565404236316680908203126")
+        );
+    }
+
+    #[test]
+    fn number_f64_matches_std() {
+        let inputs = [
+            "123456789012345", "1234567890123456", "0.000123456789012345",
+            "987654321e-22", "987654321e22", "3.14159265358979323846",
+            "-2.5e-5", "4.35", "1e23", "8.98846567431158e307",
+        ];
+        for input in inputs.iter() {
+            assert_eq!(Ok(input.parse::<f64>().unwrap()), parse_f64(input.as_bytes()));
+        }
+    }
+
+    fn number(text: &str) -> JsonNumber {
+        JsonNumber::from_bytes(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn json_number_parts() {
+        let value = number("-12.3400e-2");
+        assert!(value.is_negative());
+        assert_eq!("123400", value.digits());
+        assert_eq!(-6, value.exponent());
+        let mut value = value;
+        value.normalize();
+        assert_eq!("1234", value.digits());
+        assert_eq!(-4, value.exponent());
+        let mut zero = number("-0.000");
+        assert!(zero.is_zero());
+        zero.normalize();
+        assert!(!zero.is_negative());
+        assert_eq!(0, zero.exponent());
+        assert_eq!(Err(NumberError::Invalid), JsonNumber::from_bytes(b"1.e3"));
+        assert_eq!(
+            Err(NumberError::Overflow),
+            JsonNumber::from_bytes(b"1e99999999999999999999")
+        );
+    }
+
+    #[test]
+    fn json_number_decimal_string() {
+        let precise = "12345678901234567890.123456789";
+        assert_eq!(precise, number(precise).to_decimal_string());
+        assert_eq!("-0.00123", number("-1.23e-3").to_decimal_string());
+        assert_eq!("1500", number("1.5e3").to_decimal_string());
+        assert_eq!("0", number("-0.0").to_decimal_string());
+        assert_eq!("5e-1", number("0.5").to_string());
+        assert_eq!("-15e2", number("-1.500e3").to_string());
+        assert_eq!("12", number("12").to_string());
+        assert_eq!("0", number("-0.0").to_string());
+        assert_eq!("1e100000", number("1e100000").to_string());
+        assert_eq!(Some("100".to_string()), number("1.00e2").to_integer_string());
+        assert_eq!(Some("10".to_string()), number("100e-1").to_integer_string());
+        assert_eq!(None, number("1.5").to_integer_string());
+        assert_eq!(
+            Some("123456789012345678901234567890".to_string()),
+            number("123456789012345678901234567890").to_integer_string()
+        );
+    }
+
+    #[test]
+    fn json_number_i128() {
+        assert_eq!(Ok(i128::MAX), number("170141183460469231731687303715884105727").to_i128());
+        assert_eq!(Ok(i128::MIN), number("-170141183460469231731687303715884105728").to_i128());
+        assert_eq!(
+            Err(NumberError::Overflow),
+            number("170141183460469231731687303715884105728").to_i128()
+        );
+        assert_eq!(Ok(-1500), number("-1.5e3").to_i128());
+        assert_eq!(Err(NumberError::NotAnInteger), number("1.5").to_i128());
+        assert_eq!(Ok(0), number("0e-5").to_i128());
+    }
+
+    #[test]
+    fn json_number_f64() {
+        assert_eq!(Ok(0.1), number("0.1").to_f64());
+        assert_eq!(Ok(-1500.0), number("-1.50e3").to_f64());
+        assert!(number("-0").to_f64().unwrap().is_sign_negative());
+    }
+
+    #[test]
+    fn json_number_compare() {
+        assert_eq!(number("1.50"), number("15e-1"));
+        assert_eq!(number("0"), number("-0.0e10"));
+        assert!(number("12345678901234567890.123456789") > number("12345678901234567890.123456788"));
+        assert!(number("-2") < number("-1"));
+        assert!(number("-1") < number("0"));
+        assert!(number("0") < number("1e-100"));
+        assert!(number("99") < number("1e2"));
+        assert!(number("1.01") > number("1.001"));
+        assert!(number("-1.01") < number("-1.001"));
+        let mut values = [number("3"), number("-1e3"), number("0.5"), number("2e0")];
+        values.sort();
+        let sorted: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        assert_eq!(vec!["-1e3", "5e-1", "2", "3"], sorted);
+    }
+}
@@ -0,0 +1,185 @@
+use parse::Node;
+use pointer::JsonPointer;
+use strings::unescape_bytes;
+use value::{JsonKind, ValueRef};
+
+/// Used to mark the root node which doesn't have a parent.
+const NO_PARENT: u32 = !0;
+
+
+/// The parent and byte offset of every node in parsed JSON.
+///
+/// The nodes only record how many children come after them, so there is no
+/// way to get from a node back up to the object or array containing it.
+/// This computes those links in a single pass over the nodes, so that the
+/// path of any node can be reconstructed. This is useful for reporting where
+/// in a document a problem was found.
+pub struct ParentMap<'a> {
+    bytes: &'a [u8],
+    nodes: &'a [Node],
+    parents: Vec<u32>,
+    offsets: Vec<u32>,
+}
+
+/// An object or array which we are still finding the children of.
+struct Container {
+    index: usize,
+    end: usize,
+    next_offset: usize,
+}
+
+impl<'a> ParentMap<'a> {
+    /// Compute the parent and offset of every node.
+    pub fn new(bytes: &'a [u8], nodes: &'a [Node]) -> ParentMap<'a> {
+        let mut parents = Vec::with_capacity(nodes.len());
+        let mut offsets = Vec::with_capacity(nodes.len());
+        let mut stack: Vec<Container> = Vec::new();
+        for (index, node) in nodes.iter().enumerate() {
+            // Pop the containers which ended before this node.
+            while stack.last().map_or(false, |container| container.end < index) {
+                stack.pop();
+            }
+            let offset = match stack.last_mut() {
+                Some(container) => {
+                    parents.push(container.index as u32);
+                    let offset = container.next_offset;
+                    container.next_offset += node.length_in_bytes as usize + 1;
+                    offset
+                },
+                None => {
+                    parents.push(NO_PARENT);
+                    0
+                },
+            };
+            offsets.push(offset as u32);
+            if node.children > 0 {
+                stack.push(Container {
+                    index,
+                    end: index + node.children as usize,
+                    next_offset: offset + 1,
+                });
+            }
+        }
+        ParentMap { bytes, nodes, parents, offsets }
+    }
+
+    /// The index of the object or array containing a node. Returns `None`
+    /// for the root node.
+    pub fn parent(&self, index: usize) -> Option<usize> {
+        match self.parents[index] {
+            NO_PARENT => None,
+            parent => Some(parent as usize),
+        }
+    }
+
+    /// The offset of a node in the compacted JSON bytes.
+    pub fn offset(&self, index: usize) -> usize {
+        self.offsets[index] as usize
+    }
+
+    /// Get a reference to the value for any node.
+    pub fn value(&self, index: usize) -> ValueRef<'a> {
+        ValueRef::new(self.bytes, self.nodes, index, self.offset(index))
+    }
+
+    /// Build a JSON Pointer to a node from the root. The pointer for an
+    /// object key is the same as the pointer for its value. Returns `None`
+    /// if the index is out of range.
+    pub fn path_of(&self, index: usize) -> Option<JsonPointer> {
+        if index >= self.nodes.len() {
+            return None;
+        }
+        let mut tokens = Vec::new();
+        let mut index = index;
+        while let Some(parent) = self.parent(index) {
+            let container = self.value(parent);
+            if container.kind() == JsonKind::Object {
+                let mut members = container.object_iter().unwrap();
+                let key = loop {
+                    let (key, value) = members.next().unwrap();
+                    if key.index() == index || value.index() == index {
+                        break key;
+                    }
+                };
+                let escaped = key.escaped_str().unwrap_or(b"");
+                let name = match unescape_bytes(escaped) {
+                    Some(unescaped) => String::from_utf8_lossy(&unescaped).into_owned(),
+                    None => String::from_utf8_lossy(escaped).into_owned(),
+                };
+                tokens.push(name);
+            } else {
+                let position = container.array_iter().unwrap()
+                    .position(|element| element.index() == index)
+                    .unwrap();
+                tokens.push(position.to_string());
+            }
+            index = parent;
+        }
+        let mut pointer = JsonPointer::new();
+        for token in tokens.into_iter().rev() {
+            pointer.push(token);
+        }
+        Some(pointer)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::ParentMap;
+    use document::Document;
+
+    const DOCUMENT: &[u8] = br#"{
+        "content": {"body": "hi", "a/b": [1, {"x": [true]}]},
+        "type": "m.text"
+    }"#;
+
+    #[test]
+    fn parents_and_offsets() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        let map = ParentMap::new(document.bytes(), document.nodes());
+        let expected_parents = [
+            None, Some(0), Some(0), Some(2), Some(2), Some(2), Some(2),
+            Some(6), Some(6), Some(8), Some(8), Some(10), Some(0), Some(0),
+        ];
+        assert_eq!(expected_parents.len(), document.nodes().len());
+        for (index, parent) in expected_parents.iter().enumerate() {
+            assert_eq!(*parent, map.parent(index));
+        }
+        let root = document.root().unwrap();
+        let content = root.get("content").unwrap();
+        assert_eq!(content.offset(), map.offset(content.index()));
+        let value = content.get("a/b").unwrap().get_index(1).unwrap();
+        assert_eq!(value.offset(), map.offset(value.index()));
+        assert_eq!(br#"{"x":[true]}"#, map.value(value.index()).raw_bytes());
+    }
+
+    #[test]
+    fn path_of() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        let map = ParentMap::new(document.bytes(), document.nodes());
+        let paths = [
+            "", "/content", "/content", "/content/body", "/content/body",
+            "/content/a~1b", "/content/a~1b", "/content/a~1b/0",
+            "/content/a~1b/1", "/content/a~1b/1/x", "/content/a~1b/1/x",
+            "/content/a~1b/1/x/0", "/type", "/type",
+        ];
+        let keys = [1, 3, 5, 9, 12];
+        let root = document.root().unwrap();
+        for (index, path) in paths.iter().enumerate() {
+            let pointer = map.path_of(index).unwrap();
+            assert_eq!(*path, pointer.to_string());
+            // The pointer for a key refers to the value after it.
+            let expected = if keys.contains(&index) { index + 1 } else { index };
+            assert_eq!(expected, pointer.resolve(root).unwrap().index());
+        }
+        assert!(map.path_of(paths.len()).is_none());
+    }
+
+    #[test]
+    fn path_of_escaped_key() {
+        let document = Document::from_slice(br#"{"a\"b\u0001":[0,1]}"#).unwrap();
+        let map = ParentMap::new(document.bytes(), document.nodes());
+        assert_eq!("/a\"b\u{1}/1", map.path_of(4).unwrap().to_string());
+    }
+}
@@ -12,7 +12,7 @@
  * See the License for the specific language governing permissions and
  * limitations under the License.
  */
-
+use error::{Error, ErrorKind};
 
 /// Parsed JSON is stored as a byte array of compact JSON and an array of nodes.
 /// Each node represents a JSON object, array or value.
@@ -56,22 +56,18 @@ pub struct Stack {
     parsing_object: bool,
 }
 
-pub fn parse(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> Result<(),()> {
-    if parse_(input, output, stack) {
-        Ok(())
-    } else {
-        Err(())
-    }
-}
-
-fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool {
+/// Parse compacted JSON into an array of nodes. Fails if the input ended
+/// before the root object or array was closed, if a string was unterminated,
+/// or if there was data after the end of the root. The parser doesn't check
+/// anything else, see `validate::validate`.
+pub fn parse(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> Result<(), Error> {
     let mut iter = input.iter().peekable();
     let mut parsing_object = false;
     if iter.len() == 2 {
         // If the input is two bytes long then it is an empty object
         // or an empty array.
         push_node(output, 2);
-        return true;
+        return Ok(());
     }
     'node_end: loop {
         match stack.pop() {
@@ -90,7 +86,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // This lookup should never fail.
                 let node = match output.get_mut(offset as usize) {
                     Some(node) => node,
-                    None => return false
+                    None => return Err(unexpected_end(input))
                 };
                 // The node length contined the number of bytes left in the
                 // iterator when the node began. Subtracting the number of
@@ -103,7 +99,13 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // array and we can return.
                 let prev_offset_and_state = match stack.last() {
                     Some(value) => value,
-                    None => return true // <-- This is where the parser exits.
+                    // This is where the parser exits.
+                    None => return match iter.len() {
+                        0 => Ok(()),
+                        left => Err(Error::new(
+                            ErrorKind::TrailingData, input.len() - left
+                        )),
+                    },
                 };
                 // Whether we were parsing an object or and array is stored in
                 // the first bit of the stack entry.
@@ -112,7 +114,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // followed by more stuff in the outer node. or the outer node
                 // is ending with a b']' or a b'}'.
                 let input_char = match iter.next() {
-                    None => return false,
+                    None => return Err(unexpected_end(input)),
                     Some(value) => *value
                 };
                 // If the node ends then jump to handling the end of a node.
@@ -127,31 +129,31 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // If we are parsing an object then parse the string key.
                 let start = iter.len();
                 // We can assume it's a string so skip the opening b'"'.
-                if iter.next() == None {
-                    return false;
+                if iter.next().is_none() {
+                    return Err(unexpected_end(input));
                 }
                 // Loop through the characters until we find a closing b'"'.
                 if !parse_string(&mut iter) {
-                    return false;
+                    return Err(unterminated_string(input, start));
                 }
                 // Add a node with the string.
                 push_node(output, start - iter.len());
                 // Skip over the b':'.
-                if iter.next() == None {
-                    return false;
+                if iter.next().is_none() {
+                    return Err(unexpected_end(input));
                 }
             }
             // Parse a JSON value.
             let start = iter.len();
             let input_char = match iter.next() {
-                None => return false,
+                None => return Err(unexpected_end(input)),
                 Some(value) => *value
             };
             if input_char == b'{' {
                 // This is the start of a JSON object.
                 // Look at the next char to check if the object is empty.
                 let peek_char = match iter.peek() {
-                    None => return false,
+                    None => return Err(unexpected_end(input)),
                     Some(value) => **value
                 };
                 if peek_char == b'}' {
@@ -178,7 +180,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // This is the start of a JSON array.
                 // Look at the next char to check if the array is empty.
                 let peek_char = match iter.peek() {
-                    None => return false,
+                    None => return Err(unexpected_end(input)),
                     Some(value) => **value
                 };
                 if peek_char == b']' {
@@ -204,7 +206,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
             } else if input_char == b'"' {
                 // We are parsing a string. Loop until we see a closing b'"'.
                 if !parse_string(&mut iter) {
-                    return false;
+                    return Err(unterminated_string(input, start));
                 }
                 push_node(output, start - iter.len());
             } else {
@@ -212,7 +214,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
                 // Loop until we see a b',', a b'}', or a b']'.
                 loop {
                     let input_char = match iter.next() {
-                        None => return false,
+                        None => return Err(unexpected_end(input)),
                         Some(value) => *value
                     };
                     if input_char == b',' {
@@ -232,7 +234,7 @@ fn parse_(input: &[u8], output: &mut Vec<Node>, stack: &mut Vec<Stack>) -> bool
             // to parse in the containing object or array or the character
             // is a b']' or a b'}' if the contaning object or array is ending.
             let input_char = match iter.next() {
-                None => return false,
+                None => return Err(unexpected_end(input)),
                 Some(value) => *value
             };
             if input_char == b',' {
@@ -263,6 +265,15 @@ fn parse_string<'a, T: Iterator<Item=&'a u8>>(iter: &mut T) -> bool {
     true
 }
 
+fn unexpected_end(input: &[u8]) -> Error {
+    Error::new(ErrorKind::UnexpectedEnd, input.len())
+}
+
+/// The string started when there were `start` bytes of the input left.
+fn unterminated_string(input: &[u8], start: usize) -> Error {
+    Error::new(ErrorKind::UnterminatedString, input.len() - start)
+}
+
 fn push_node(output: &mut Vec<Node>, len : usize) {
     output.push( Node {
         children: 0,
@@ -275,6 +286,7 @@ fn push_node(output: &mut Vec<Node>, len : usize) {
 mod test {
     use super::Node;
     use super::Stack;
+    use error::ErrorKind;
 
     fn parse(input: &[u8]) -> Vec<Node> {
         let mut output : Vec<Node> = Vec::new();
@@ -326,4 +338,19 @@ mod test {
             Node {children: 0, length_in_bytes: 4},
         ], result);
     }
+
+    fn parse_error(input: &[u8]) -> (ErrorKind, usize) {
+        let mut output : Vec<Node> = Vec::new();
+        let mut stack : Vec<Stack> = Vec::new();
+        let error = super::parse(input, &mut output, &mut stack).unwrap_err();
+        (error.kind(), error.offset())
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!((ErrorKind::UnexpectedEnd, 6), parse_error(b"[[1],2"));
+        assert_eq!((ErrorKind::UnexpectedEnd, 4), parse_error(br#"{"a""#));
+        assert_eq!((ErrorKind::UnterminatedString, 5), parse_error(br#"{"a":"b}"#));
+        assert_eq!((ErrorKind::TrailingData, 3), parse_error(b"[1]]"));
+    }
 }
@@ -0,0 +1,226 @@
+use std::fmt;
+
+use value::{JsonKind, ValueRef};
+
+
+/// Resolve an RFC 6901 JSON Pointer like `"/a/b/0"` starting from a value.
+/// Returns `None` if the pointer is malformed or doesn't refer to anything.
+///
+/// The pointer is resolved by skipping along the siblings in each object or
+/// array on the path, so values which aren't on the path are never decoded.
+/// Keys are compared with the escaped keys in the JSON without unescaping
+/// them.
+pub fn resolve<'a>(root: ValueRef<'a>, pointer: &str) -> Option<ValueRef<'a>> {
+    if pointer.is_empty() {
+        // The empty pointer refers to the whole document.
+        return Some(root);
+    }
+    if !pointer.starts_with('/') {
+        return None;
+    }
+    let mut value = root;
+    let mut buffer: Vec<u8> = Vec::new();
+    for token in pointer[1..].split('/') {
+        let token = token.as_bytes();
+        if token.contains(&b'~') {
+            if !unescape_token(token, &mut buffer) {
+                return None;
+            }
+            value = step(value, &buffer)?;
+        } else {
+            value = step(value, token)?;
+        }
+    }
+    Some(value)
+}
+
+/// Follow a single unescaped reference token from an object or an array.
+fn step<'a>(value: ValueRef<'a>, token: &[u8]) -> Option<ValueRef<'a>> {
+    match value.kind() {
+        JsonKind::Object => value.get_bytes(token),
+        JsonKind::Array => value.get_index(parse_index(token)?),
+        _ => None,
+    }
+}
+
+
+/// A JSON Pointer held as a list of unescaped reference tokens. Formatting
+/// the pointer with `Display` produces the RFC 6901 string form, escaping
+/// `~` as `~0` and `/` as `~1`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct JsonPointer {
+    tokens: Vec<String>,
+}
+
+impl JsonPointer {
+    /// Create a pointer referring to the whole document.
+    pub fn new() -> JsonPointer {
+        JsonPointer { tokens: Vec::new() }
+    }
+
+    /// Add an object key or array index to the end of the pointer.
+    pub fn push<T: Into<String>>(&mut self, token: T) {
+        self.tokens.push(token.into());
+    }
+
+    /// The unescaped reference tokens.
+    pub fn tokens(&self) -> &[String] {
+        &self.tokens
+    }
+
+    /// Resolve this pointer starting from a value. See `resolve`.
+    pub fn resolve<'a>(&self, root: ValueRef<'a>) -> Option<ValueRef<'a>> {
+        let mut value = root;
+        for token in &self.tokens {
+            value = step(value, token.as_bytes())?;
+        }
+        Some(value)
+    }
+}
+
+impl fmt::Display for JsonPointer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for token in &self.tokens {
+            f.write_str("/")?;
+            for c in token.chars() {
+                match c {
+                    '~' => f.write_str("~0")?,
+                    '/' => f.write_str("~1")?,
+                    _ => fmt::Write::write_char(f, c)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace `~1` with `/` and `~0` with `~` in a reference token.
+/// Returns false if the token contains any other `~` escape.
+fn unescape_token(token: &[u8], output: &mut Vec<u8>) -> bool {
+    output.clear();
+    let mut iter = token.iter();
+    while let Some(&c) = iter.next() {
+        if c != b'~' {
+            output.push(c);
+            continue;
+        }
+        match iter.next() {
+            Some(&b'0') => output.push(b'~'),
+            Some(&b'1') => output.push(b'/'),
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Parse an array index. Indices are either `0` or a digit `1`...`9`
+/// followed by zero or more digits. The `-` index refers to the element
+/// after the end of the array so it never refers to an existing value.
+fn parse_index(token: &[u8]) -> Option<usize> {
+    if token.is_empty() || (token[0] == b'0' && token.len() > 1) {
+        return None;
+    }
+    let mut index: usize = 0;
+    for c in token {
+        match *c {
+            b'0'..=b'9' => {
+                index = index.checked_mul(10)?.checked_add((c - b'0') as usize)?;
+            },
+            _ => return None,
+        }
+    }
+    Some(index)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::JsonPointer;
+    use document::Document;
+
+    const DOCUMENT: &[u8] = br#"{
+        "foo": ["bar", "baz"],
+        "": 0,
+        "a/b": 1,
+        "c%d": 2,
+        "e^f": 3,
+        "g|h": 4,
+        "i\\j": 5,
+        "k\"l": 6,
+        " ": 7,
+        "m~n": 8,
+        "content": {"m.relates_to": {"event_id": "$event"}}
+    }"#;
+
+    fn pointer(document: &Document, pointer: &str) -> Option<Vec<u8>> {
+        document.pointer(pointer).map(|value| value.raw_bytes().to_vec())
+    }
+
+    #[test]
+    fn pointer_rfc_examples() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        assert_eq!(Some(document.bytes().to_vec()), pointer(&document, ""));
+        assert_eq!(Some(br#"["bar","baz"]"#.to_vec()), pointer(&document, "/foo"));
+        assert_eq!(Some(br#""bar""#.to_vec()), pointer(&document, "/foo/0"));
+        assert_eq!(Some(b"0".to_vec()), pointer(&document, "/"));
+        assert_eq!(Some(b"1".to_vec()), pointer(&document, "/a~1b"));
+        assert_eq!(Some(b"2".to_vec()), pointer(&document, "/c%d"));
+        assert_eq!(Some(b"3".to_vec()), pointer(&document, "/e^f"));
+        assert_eq!(Some(b"4".to_vec()), pointer(&document, "/g|h"));
+        assert_eq!(Some(b"5".to_vec()), pointer(&document, "/i\\j"));
+        assert_eq!(Some(b"6".to_vec()), pointer(&document, "/k\"l"));
+        assert_eq!(Some(b"7".to_vec()), pointer(&document, "/ "));
+        assert_eq!(Some(b"8".to_vec()), pointer(&document, "/m~0n"));
+    }
+
+    #[test]
+    fn pointer_nested() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        assert_eq!(
+            Some(br#""$event""#.to_vec()),
+            pointer(&document, "/content/m.relates_to/event_id")
+        );
+        assert_eq!(Some(br#""baz""#.to_vec()), pointer(&document, "/foo/1"));
+    }
+
+    #[test]
+    fn pointer_missing() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        assert_eq!(None, pointer(&document, "foo"));
+        assert_eq!(None, pointer(&document, "/bar"));
+        assert_eq!(None, pointer(&document, "/foo/2"));
+        assert_eq!(None, pointer(&document, "/foo/-"));
+        assert_eq!(None, pointer(&document, "/foo/01"));
+        assert_eq!(None, pointer(&document, "/foo/1a"));
+        assert_eq!(None, pointer(&document, "/foo/99999999999999999999999"));
+        assert_eq!(None, pointer(&document, "/foo/0/0"));
+        assert_eq!(None, pointer(&document, "/m~2n"));
+        assert_eq!(None, pointer(&document, "/m~"));
+        assert!(Document::new().pointer("").is_none());
+    }
+
+    #[test]
+    fn pointer_display() {
+        let mut pointer = JsonPointer::new();
+        assert_eq!("", pointer.to_string());
+        pointer.push("content");
+        pointer.push("a/b~c");
+        pointer.push("0");
+        assert_eq!("/content/a~1b~0c/0", pointer.to_string());
+    }
+
+    #[test]
+    fn pointer_resolve_tokens() {
+        let document = Document::from_slice(DOCUMENT).unwrap();
+        let root = document.root().unwrap();
+        let mut pointer = JsonPointer::new();
+        pointer.push("a/b");
+        assert_eq!(b"1", pointer.resolve(root).unwrap().raw_bytes());
+        let mut pointer = JsonPointer::new();
+        pointer.push("foo");
+        pointer.push("1");
+        assert_eq!(br#""baz""#, pointer.resolve(root).unwrap().raw_bytes());
+        pointer.push("x");
+        assert!(pointer.resolve(root).is_none());
+    }
+}
@@ -0,0 +1,357 @@
+use parse::Node;
+use value::ValueRef;
+use visit::{walk, Visit, Visitor};
+
+
+/// What to indent nested values with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Indent {
+    /// Indent each level by this many spaces.
+    Spaces(usize),
+    /// Indent each level by this many tabs.
+    Tabs(usize),
+}
+
+/// How to lay out pretty printed JSON.
+#[derive(Clone, Debug)]
+pub struct PrettyOptions {
+    pub indent: Indent,
+    /// Put a space between an object key and the `:`.
+    pub space_before_colon: bool,
+    /// Put a space between the `:` and the value.
+    pub space_after_colon: bool,
+    /// End the output with a new line.
+    pub trailing_newline: bool,
+    /// Write empty objects and arrays as `{}` and `[]` rather than putting
+    /// the closing bracket on the next line.
+    pub inline_empty: bool,
+    /// Write arrays with at most this many elements on one line if none of
+    /// the elements are non-empty objects or arrays, like `[1, 2, 3]`.
+    /// Zero puts every element on its own line.
+    pub inline_scalar_arrays: usize,
+    /// Write any object or array on one line if it fits within this many
+    /// columns, including the indentation and key before it and any comma
+    /// after it. Otherwise put each of its values on its own line and try
+    /// again with each of them. Columns count bytes, so a tab counts as one
+    /// column. `None` only puts objects and arrays on one line if they are
+    /// empty or short arrays of scalars.
+    pub max_line_width: Option<usize>,
+}
+
+impl Default for PrettyOptions {
+    fn default() -> PrettyOptions {
+        PrettyOptions {
+            indent: Indent::Spaces(2),
+            space_before_colon: false,
+            space_after_colon: true,
+            trailing_newline: false,
+            inline_empty: true,
+            inline_scalar_arrays: 0,
+            max_line_width: None,
+        }
+    }
+}
+
+
+/// Write compacted and parsed JSON with new lines and indentation so that a
+/// person can read it. The strings, numbers and literals are copied from the
+/// compacted JSON as they are, only the whitespace between them changes.
+/// Nothing is written if there are no nodes.
+pub fn write(bytes: &[u8], nodes: &[Node], options: &PrettyOptions, output: &mut Vec<u8>) {
+    let root = match ValueRef::root(bytes, nodes) {
+        Some(root) => root,
+        None => return,
+    };
+    let line_start = output.len();
+    let mut printer = Printer {
+        options,
+        output,
+        levels: Vec::new(),
+        after_key: false,
+        line_start,
+    };
+    walk(root, &mut printer);
+    if options.trailing_newline {
+        printer.output.push(b'\n');
+    }
+}
+
+
+/// An object or array that the printer is inside.
+struct Level {
+    /// Nothing has been written inside the object or array yet.
+    first: bool,
+    /// The object or array is being written on a single line.
+    inline: bool,
+}
+
+struct Printer<'o> {
+    options: &'o PrettyOptions,
+    output: &'o mut Vec<u8>,
+    levels: Vec<Level>,
+    /// The next value follows a key, so it goes on the same line.
+    after_key: bool,
+    /// The offset in the output of the start of the current line.
+    line_start: usize,
+}
+
+impl<'o> Printer<'o> {
+    /// Write the separator before the next key or array element.
+    fn separate(&mut self) {
+        let depth = self.levels.len();
+        let (first, inline) = match self.levels.last_mut() {
+            None => return,
+            Some(level) => {
+                let first = level.first;
+                level.first = false;
+                (first, level.inline)
+            },
+        };
+        if !first {
+            self.output.push(b',');
+        }
+        if !inline {
+            self.newline(depth);
+        } else if !first {
+            self.output.push(b' ');
+        }
+    }
+
+    fn start_value(&mut self) {
+        if self.after_key {
+            self.after_key = false;
+        } else {
+            self.separate();
+        }
+    }
+
+    fn open(&mut self, value: ValueRef, bracket: u8, inline: bool) {
+        self.start_value();
+        let inline = inline
+            || self.levels.last().map_or(false, |level| level.inline)
+            || self.fits(value);
+        self.output.push(bracket);
+        self.levels.push(Level { first: true, inline });
+    }
+
+    fn close(&mut self, bracket: u8) {
+        if let Some(level) = self.levels.pop() {
+            if !level.inline {
+                let depth = self.levels.len();
+                self.newline(depth);
+            }
+        }
+        self.output.push(bracket);
+    }
+
+    fn newline(&mut self, depth: usize) {
+        self.output.push(b'\n');
+        self.line_start = self.output.len();
+        let (byte, width) = match self.options.indent {
+            Indent::Spaces(width) => (b' ', width),
+            Indent::Tabs(width) => (b'\t', width),
+        };
+        let length = self.output.len() + width * depth;
+        self.output.resize(length, byte);
+    }
+
+    fn is_short_scalar_array(&self, array: ValueRef) -> bool {
+        let limit = self.options.inline_scalar_arrays;
+        // Scalars don't have children so the array only has scalars if it
+        // has as many children as elements.
+        let children = array.children_count();
+        children <= limit && array.array_iter().unwrap().count() == children
+    }
+
+    /// Whether a non-empty object or array fits on the rest of the line.
+    fn fits(&self, value: ValueRef) -> bool {
+        let max_line_width = match self.options.max_line_width {
+            None => return false,
+            Some(max_line_width) => max_line_width,
+        };
+        let column = self.output.len() - self.line_start;
+        // The next value is separated by a comma which has to fit as well.
+        let comma = match value.document_bytes().get(value.end_offset()) {
+            Some(&b',') => 1,
+            _ => 0,
+        };
+        let available = match max_line_width.checked_sub(column + comma) {
+            None => return false,
+            Some(available) => available,
+        };
+        // The compacted value is as narrow as it can be written, so only
+        // count the separators if it might fit.
+        let compact = value.node().length_in_bytes as usize;
+        if compact > available {
+            return false;
+        }
+        let mut separators = Separators { keys: 0, containers: 0 };
+        walk(value, &mut separators);
+        // Every node inside a container but the first is preceded by a comma
+        // or a colon.
+        let commas = value.children_count() - separators.containers - separators.keys;
+        let colon = self.options.space_before_colon as usize
+            + self.options.space_after_colon as usize;
+        compact + commas + separators.keys * colon <= available
+    }
+}
+
+
+/// Counts the nodes inside a value which are written with separators.
+struct Separators {
+    keys: usize,
+    /// The number of non-empty objects and arrays.
+    containers: usize,
+}
+
+impl<'a> Visitor<'a> for Separators {
+    fn enter_object(&mut self, object: ValueRef<'a>) -> Visit {
+        if !object.is_empty_container() {
+            self.containers += 1;
+        }
+        Visit::Continue
+    }
+
+    fn key(&mut self, _key: ValueRef<'a>) {
+        self.keys += 1;
+    }
+
+    fn enter_array(&mut self, array: ValueRef<'a>) -> Visit {
+        if !array.is_empty_container() {
+            self.containers += 1;
+        }
+        Visit::Continue
+    }
+}
+
+impl<'a, 'o> Visitor<'a> for Printer<'o> {
+    fn enter_object(&mut self, object: ValueRef<'a>) -> Visit {
+        let inline = object.is_empty_container() && self.options.inline_empty;
+        self.open(object, b'{', inline);
+        Visit::Continue
+    }
+
+    fn key(&mut self, key: ValueRef<'a>) {
+        self.separate();
+        self.output.extend_from_slice(key.raw_bytes());
+        if self.options.space_before_colon {
+            self.output.push(b' ');
+        }
+        self.output.push(b':');
+        if self.options.space_after_colon {
+            self.output.push(b' ');
+        }
+        self.after_key = true;
+    }
+
+    fn enter_array(&mut self, array: ValueRef<'a>) -> Visit {
+        let inline = if array.is_empty_container() {
+            self.options.inline_empty
+        } else {
+            self.is_short_scalar_array(array)
+        };
+        self.open(array, b'[', inline);
+        Visit::Continue
+    }
+
+    fn scalar(&mut self, value: ValueRef<'a>) {
+        self.start_value();
+        self.output.extend_from_slice(value.raw_bytes());
+    }
+
+    fn exit_object(&mut self, _object: ValueRef<'a>) {
+        self.close(b'}');
+    }
+
+    fn exit_array(&mut self, _array: ValueRef<'a>) {
+        self.close(b']');
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::{write, Indent, PrettyOptions};
+    use document::Document;
+
+    fn pretty(input: &str, options: &PrettyOptions) -> String {
+        let document = Document::from_slice(input.as_bytes()).unwrap();
+        let mut output = Vec::new();
+        write(document.bytes(), document.nodes(), options, &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    const INPUT: &str = r#"{"a":[1,"two",{}],"b":{"c":[],"d":null}}"#;
+
+    #[test]
+    fn pretty_default() {
+        assert_eq!(
+            "{\n  \"a\": [\n    1,\n    \"two\",\n    {}\n  ],\n  \
+             \"b\": {\n    \"c\": [],\n    \"d\": null\n  }\n}",
+            pretty(INPUT, &PrettyOptions::default())
+        );
+        assert_eq!("[]", pretty("[]", &PrettyOptions::default()));
+    }
+
+    #[test]
+    fn pretty_options() {
+        let options = PrettyOptions {
+            indent: Indent::Tabs(1),
+            space_before_colon: true,
+            space_after_colon: false,
+            trailing_newline: true,
+            inline_empty: false,
+            inline_scalar_arrays: 0,
+            max_line_width: None,
+        };
+        assert_eq!(
+            "{\n\t\"a\" :[\n\t\t1,\n\t\t\"two\",\n\t\t{\n\t\t}\n\t],\n\
+             \t\"b\" :{\n\t\t\"c\" :[\n\t\t],\n\t\t\"d\" :null\n\t}\n}\n",
+            pretty(INPUT, &options)
+        );
+        let options = PrettyOptions { indent: Indent::Spaces(4), ..PrettyOptions::default() };
+        assert_eq!("[\n    [\n        1\n    ]\n]", pretty("[[1]]", &options));
+    }
+
+    #[test]
+    fn pretty_scalar_arrays() {
+        let options = PrettyOptions { inline_scalar_arrays: 3, ..PrettyOptions::default() };
+        assert_eq!(
+            "{\n  \"a\": [1, \"two\", {}],\n  \
+             \"b\": [\n    1,\n    2,\n    3,\n    4\n  ],\n  \
+             \"c\": [\n    [1]\n  ]\n}",
+            pretty(r#"{"a":[1,"two",{}],"b":[1,2,3,4],"c":[[1]]}"#, &options)
+        );
+    }
+
+    #[test]
+    fn pretty_max_line_width() {
+        let input = r#"{"short":{"a":1,"b":[2,3]},"long":[[1,2,3,4],{"k":"v"}],"e":[]}"#;
+        let options = PrettyOptions { max_line_width: Some(26), ..PrettyOptions::default() };
+        // `  "short": {"a": 1, "b": [2, 3]},` is 33 columns wide so is broken
+        // but `  "long": [[1, 2, 3, 4], {"k": "v"}],` can't fit at all.
+        assert_eq!(
+            "{\n  \"short\": {\n    \"a\": 1,\n    \"b\": [2, 3]\n  },\n  \
+             \"long\": [\n    [1, 2, 3, 4],\n    {\"k\": \"v\"}\n  ],\n  \"e\": []\n}",
+            pretty(input, &options)
+        );
+        let options = PrettyOptions { max_line_width: Some(33), ..PrettyOptions::default() };
+        assert!(pretty(input, &options).contains("\n  \"short\": {\"a\": 1, \"b\": [2, 3]},\n"));
+        let options = PrettyOptions { max_line_width: Some(32), ..PrettyOptions::default() };
+        assert!(!pretty(input, &options).contains("\"short\": {\"a\""));
+        // The whole document fits.
+        let options = PrettyOptions { max_line_width: Some(80), ..PrettyOptions::default() };
+        assert_eq!(
+            r#"{"short": {"a": 1, "b": [2, 3]}, "long": [[1, 2, 3, 4], {"k": "v"}], "e": []}"#,
+            pretty(input, &options)
+        );
+    }
+
+    #[test]
+    fn pretty_deeply_nested() {
+        let input = "[".repeat(10000) + &"]".repeat(10000);
+        let output = pretty(&input, &PrettyOptions::default());
+        // Every bracket gets a line except the empty innermost array.
+        assert_eq!(19999, output.lines().count());
+    }
+}
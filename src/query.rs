@@ -0,0 +1,917 @@
+use std::borrow::Cow;
+use std::error;
+use std::fmt;
+
+use strings::unescape_bytes;
+use value::{JsonKind, ValueRef};
+
+
+/// A compiled JSONPath expression.
+///
+/// Supports the root `$`, child segments `.name`, `['name']` and `.*`,
+/// recursive descent `..`, array indices `[0]` and `[-1]`, slices
+/// `[start:end:step]`, unions `[0,'a',1:3]` and filters like
+/// `[?(@.type == 'm.room.message')]`.
+///
+/// Filters can test whether a path exists, `[?(@.a)]`, or compare a path
+/// with a literal or another path using `==`, `!=`, `<`, `<=`, `>` and
+/// `>=`. Tests can be combined with `&&`, `||`, `!` and parentheses. The
+/// paths in a filter must refer to at most one value, so they can only
+/// contain names and indices.
+///
+/// Strings are compared by their unescaped contents and numbers are compared
+/// numerically. Objects and arrays are only equal if their compacted JSON is
+/// identical.
+#[derive(Debug)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+/// The reason a JSONPath expression couldn't be compiled and the offset in
+/// the expression where the problem was found.
+#[derive(PartialEq, Debug)]
+pub struct QueryError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.offset)
+    }
+}
+
+impl error::Error for QueryError {}
+
+#[derive(Debug)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug)]
+enum Selector {
+    Name(Vec<u8>),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Filter),
+}
+
+#[derive(Debug)]
+enum Filter {
+    Or(Vec<Filter>),
+    And(Vec<Filter>),
+    Not(Box<Filter>),
+    Exists(Path),
+    Compare(Operand, Comparison, Operand),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+enum Operand {
+    Path(Path),
+    Literal(Literal),
+}
+
+#[derive(Debug)]
+enum Literal {
+    String(Vec<u8>),
+    Number(f64),
+    Bool(bool),
+    Null,
+}
+
+/// A path inside a filter which refers to at most one value. Either
+/// relative to the current value `@` or to the root `$`.
+#[derive(Debug)]
+struct Path {
+    from_root: bool,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Name(Vec<u8>),
+    Index(i64),
+}
+
+impl Query {
+    /// Compile a JSONPath expression.
+    pub fn compile(expression: &str) -> Result<Query, QueryError> {
+        let mut parser = Parser { input: expression.as_bytes(), pos: 0, depth: 0 };
+        parser.skip_whitespace();
+        parser.expect(b'$', "expected '$'")?;
+        let mut segments = Vec::new();
+        loop {
+            parser.skip_whitespace();
+            match parser.peek() {
+                None => break,
+                Some(b'.') if parser.peek_at(1) == Some(b'.') => {
+                    parser.pos += 2;
+                    let selectors = match parser.peek() {
+                        Some(b'[') => parser.bracketed_selectors()?,
+                        _ => vec![parser.dot_selector()?],
+                    };
+                    segments.push(Segment::Descendant(selectors));
+                },
+                Some(b'.') => {
+                    parser.pos += 1;
+                    segments.push(Segment::Child(vec![parser.dot_selector()?]));
+                },
+                Some(b'[') => {
+                    segments.push(Segment::Child(parser.bracketed_selectors()?));
+                },
+                _ => return Err(parser.error("expected '.' or '['")),
+            }
+        }
+        Ok(Query { segments })
+    }
+
+    /// Evaluate the query starting from the root value. Returns the matching
+    /// values in document order. A value matched more than once is only
+    /// returned once.
+    pub fn select<'a>(&self, root: ValueRef<'a>) -> Vec<ValueRef<'a>> {
+        let mut current = vec![root];
+        let mut next = Vec::new();
+        for segment in &self.segments {
+            for value in &current {
+                match *segment {
+                    Segment::Child(ref selectors) => {
+                        for selector in selectors {
+                            select(root, *value, selector, &mut next);
+                        }
+                    },
+                    Segment::Descendant(ref selectors) => {
+                        let mut stack = vec![*value];
+                        while let Some(value) = stack.pop() {
+                            for selector in selectors {
+                                select(root, value, selector, &mut next);
+                            }
+                            // Push the children in reverse so that they are
+                            // visited in document order.
+                            let start = stack.len();
+                            push_children(value, &mut stack);
+                            stack[start..].reverse();
+                        }
+                    },
+                }
+            }
+            next.sort_by_key(|value| value.index());
+            next.dedup_by_key(|value| value.index());
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        current
+    }
+
+    /// Evaluate the query starting from the root value. Returns the indices
+    /// of the matching nodes in document order.
+    pub fn select_indices(&self, root: ValueRef) -> Vec<usize> {
+        self.select(root).iter().map(|value| value.index()).collect()
+    }
+}
+
+/// Push the values inside an object or array onto the output.
+fn push_children<'a>(value: ValueRef<'a>, output: &mut Vec<ValueRef<'a>>) {
+    if let Some(elements) = value.array_iter() {
+        output.extend(elements);
+    } else if let Some(members) = value.object_iter() {
+        output.extend(members.map(|(_, member)| member));
+    }
+}
+
+fn select<'a>(
+    root: ValueRef<'a>, value: ValueRef<'a>, selector: &Selector,
+    output: &mut Vec<ValueRef<'a>>,
+) {
+    match *selector {
+        Selector::Name(ref name) => {
+            if let Some(member) = value.get_bytes(name) {
+                output.push(member);
+            }
+        },
+        Selector::Wildcard => push_children(value, output),
+        Selector::Index(index) => {
+            if index >= 0 {
+                if let Some(element) = value.get_index(index as usize) {
+                    output.push(element);
+                }
+            } else if let Some(elements) = value.array_iter() {
+                let elements: Vec<ValueRef<'a>> = elements.collect();
+                let len = elements.len() as i64;
+                if index + len >= 0 {
+                    output.push(elements[(index + len) as usize]);
+                }
+            }
+        },
+        Selector::Slice(start, end, step) => {
+            if let Some(elements) = value.array_iter() {
+                let elements: Vec<ValueRef<'a>> = elements.collect();
+                for index in slice_indices(elements.len() as i64, start, end, step) {
+                    output.push(elements[index]);
+                }
+            }
+        },
+        Selector::Filter(ref filter) => {
+            let mut children = Vec::new();
+            push_children(value, &mut children);
+            for child in children {
+                if test(root, child, filter) {
+                    output.push(child);
+                }
+            }
+        },
+    }
+}
+
+/// The indices selected by a slice, following the rules for negative
+/// indices and steps in RFC 9535.
+fn slice_indices(
+    len: i64, start: Option<i64>, end: Option<i64>, step: Option<i64>
+) -> Vec<usize> {
+    let step = step.unwrap_or(1);
+    let normalize = |index: i64| if index >= 0 { index } else { len + index };
+    let mut indices = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        let mut index = lower;
+        while index < upper {
+            indices.push(index as usize);
+            match index.checked_add(step) {
+                Some(next) => index = next,
+                None => break,
+            }
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).max(-1).min(len - 1);
+        let mut index = upper;
+        while lower < index {
+            indices.push(index as usize);
+            match index.checked_add(step) {
+                Some(next) => index = next,
+                None => break,
+            }
+        }
+    }
+    indices
+}
+
+fn test(root: ValueRef, current: ValueRef, filter: &Filter) -> bool {
+    match *filter {
+        Filter::Or(ref filters) => {
+            filters.iter().any(|filter| test(root, current, filter))
+        },
+        Filter::And(ref filters) => {
+            filters.iter().all(|filter| test(root, current, filter))
+        },
+        Filter::Not(ref inner) => !test(root, current, inner),
+        Filter::Exists(ref path) => resolve(root, current, path).is_some(),
+        Filter::Compare(ref left, comparison, ref right) => {
+            let left = comparable(root, current, left);
+            let right = comparable(root, current, right);
+            match comparison {
+                Comparison::Eq => left.eq(&right),
+                Comparison::Ne => !left.eq(&right),
+                Comparison::Lt => left.lt(&right),
+                Comparison::Le => left.lt(&right) || left.eq(&right),
+                Comparison::Gt => right.lt(&left),
+                Comparison::Ge => right.lt(&left) || left.eq(&right),
+            }
+        },
+    }
+}
+
+fn resolve<'a>(
+    root: ValueRef<'a>, current: ValueRef<'a>, path: &Path
+) -> Option<ValueRef<'a>> {
+    let mut value = if path.from_root { root } else { current };
+    for step in &path.steps {
+        value = match *step {
+            Step::Name(ref name) => value.get_bytes(name)?,
+            Step::Index(index) if index >= 0 => value.get_index(index as usize)?,
+            Step::Index(index) => {
+                let len = value.array_iter()?.count() as i64;
+                if index + len < 0 {
+                    return None;
+                }
+                value.get_index((index + len) as usize)?
+            },
+        };
+    }
+    Some(value)
+}
+
+/// One side of a comparison in a filter.
+enum Comparable<'a> {
+    Nothing,
+    Number(f64),
+    String(Cow<'a, [u8]>),
+    Bool(bool),
+    Null,
+    Container(&'a [u8]),
+}
+
+fn comparable<'a>(
+    root: ValueRef<'a>, current: ValueRef<'a>, operand: &'a Operand
+) -> Comparable<'a> {
+    let value = match *operand {
+        Operand::Literal(Literal::String(ref value)) => {
+            return Comparable::String(Cow::Borrowed(value));
+        },
+        Operand::Literal(Literal::Number(value)) => return Comparable::Number(value),
+        Operand::Literal(Literal::Bool(value)) => return Comparable::Bool(value),
+        Operand::Literal(Literal::Null) => return Comparable::Null,
+        Operand::Path(ref path) => match resolve(root, current, path) {
+            Some(value) => value,
+            None => return Comparable::Nothing,
+        },
+    };
+    let raw = value.raw_bytes();
+    match value.kind() {
+        JsonKind::Object | JsonKind::Array => Comparable::Container(raw),
+        JsonKind::String => {
+            let escaped = &raw[1..raw.len() - 1];
+            match unescape_bytes(escaped) {
+                Some(unescaped) => Comparable::String(unescaped),
+                None => Comparable::String(Cow::Borrowed(escaped)),
+            }
+        },
+        JsonKind::Bool => Comparable::Bool(raw[0] == b't'),
+        JsonKind::Null => Comparable::Null,
+        JsonKind::Number => match value.as_f64() {
+            Ok(number) => Comparable::Number(number),
+            Err(_) => Comparable::Nothing,
+        },
+    }
+}
+
+impl<'a> Comparable<'a> {
+    fn eq(&self, other: &Comparable) -> bool {
+        match (self, other) {
+            (Comparable::Nothing, Comparable::Nothing) => true,
+            (Comparable::Number(a), Comparable::Number(b)) => a == b,
+            (Comparable::String(a), Comparable::String(b)) => a == b,
+            (Comparable::Bool(a), Comparable::Bool(b)) => a == b,
+            (Comparable::Null, Comparable::Null) => true,
+            (Comparable::Container(a), Comparable::Container(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    fn lt(&self, other: &Comparable) -> bool {
+        match (self, other) {
+            (Comparable::Number(a), Comparable::Number(b)) => a < b,
+            (Comparable::String(a), Comparable::String(b)) => a < b,
+            _ => false,
+        }
+    }
+}
+
+
+/// How deeply `(` and `!` can be nested in a filter. Filters are parsed and
+/// tested recursively so this stops them overflowing the stack.
+const MAX_FILTER_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    /// How many `(` and `!` the filter parser is inside.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &'static str) -> QueryError {
+        QueryError { offset: self.pos, message }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn peek_at(&self, ahead: usize) -> Option<u8> {
+        self.input.get(self.pos + ahead).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: u8, message: &'static str) -> Result<(), QueryError> {
+        if self.peek() != Some(c) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// Parse the selector after a `.` or a `..`, either a `*` or a name.
+    fn dot_selector(&mut self) -> Result<Selector, QueryError> {
+        if self.peek() == Some(b'*') {
+            self.pos += 1;
+            return Ok(Selector::Wildcard);
+        }
+        Ok(Selector::Name(self.name()?))
+    }
+
+    /// Parse a member name. Names start with a letter, `_` or a non-ASCII
+    /// character which may be followed by more of those or digits.
+    fn name(&mut self) -> Result<Vec<u8>, QueryError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            let valid = c.is_ascii_alphabetic() || c == b'_' || c >= 0x80
+                || (self.pos > start && c.is_ascii_digit());
+            if !valid {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(self.error("expected a name"));
+        }
+        Ok(self.input[start..self.pos].to_vec())
+    }
+
+    /// Parse a comma separated list of selectors inside `[` `]`.
+    fn bracketed_selectors(&mut self) -> Result<Vec<Selector>, QueryError> {
+        self.expect(b'[', "expected '['")?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_whitespace();
+            selectors.push(self.selector()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(selectors);
+                },
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector, QueryError> {
+        match self.peek() {
+            Some(b'*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            },
+            Some(b'\'') | Some(b'"') => Ok(Selector::Name(self.string()?)),
+            Some(b'?') => {
+                self.pos += 1;
+                self.skip_whitespace();
+                Ok(Selector::Filter(self.filter_or()?))
+            },
+            _ => {
+                let start = self.optional_integer()?;
+                self.skip_whitespace();
+                if self.peek() != Some(b':') {
+                    return match start {
+                        Some(index) => Ok(Selector::Index(index)),
+                        None => Err(self.error("expected a selector")),
+                    };
+                }
+                self.pos += 1;
+                self.skip_whitespace();
+                let end = self.optional_integer()?;
+                self.skip_whitespace();
+                let mut step = None;
+                if self.peek() == Some(b':') {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    step = self.optional_integer()?;
+                }
+                Ok(Selector::Slice(start, end, step))
+            },
+        }
+    }
+
+    /// Parse an integer if the next character could start one.
+    fn optional_integer(&mut self) -> Result<Option<i64>, QueryError> {
+        match self.peek() {
+            Some(b'-') | Some(b'0'..=b'9') => {},
+            _ => return Ok(None),
+        }
+        let start = self.pos;
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        let digits = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        match digits.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid integer"))
+            },
+        }
+    }
+
+    /// Parse a string literal in single or double quotes. Returns the
+    /// contents as UTF-8 with the escapes decoded.
+    fn string(&mut self) -> Result<Vec<u8>, QueryError> {
+        let quote = self.input[self.pos];
+        self.pos += 1;
+        let mut output = Vec::new();
+        loop {
+            let c = match self.peek() {
+                Some(value) => value,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            if c == quote {
+                return Ok(output);
+            }
+            if c != b'\\' {
+                output.push(c);
+                continue;
+            }
+            let escaped = match self.peek() {
+                Some(value) => value,
+                None => return Err(self.error("unterminated string")),
+            };
+            self.pos += 1;
+            match escaped {
+                b'\'' | b'"' | b'\\' | b'/' => output.push(escaped),
+                b'b' => output.push(0x08),
+                b'f' => output.push(0x0C),
+                b'n' => output.push(0x0A),
+                b'r' => output.push(0x0D),
+                b't' => output.push(0x09),
+                b'u' => {
+                    let start = self.pos - 1;
+                    let mut codepoint = self.hex4()?;
+                    if (0xD800..0xDC00).contains(&codepoint)
+                        && self.peek() == Some(b'\\')
+                        && self.peek_at(1) == Some(b'u')
+                    {
+                        self.pos += 2;
+                        let surrogate = self.hex4()?;
+                        if !(0xDC00..0xE000).contains(&surrogate) {
+                            self.pos = start;
+                            return Err(self.error("invalid unicode escape"));
+                        }
+                        codepoint = 0x10000
+                            + (((codepoint & 0x3FF) << 10) | (surrogate & 0x3FF));
+                    }
+                    // Lone surrogates aren't code points.
+                    let c = match std::char::from_u32(codepoint) {
+                        Some(value) => value,
+                        None => {
+                            self.pos = start;
+                            return Err(self.error("invalid unicode escape"));
+                        },
+                    };
+                    let mut buffer = [0; 4];
+                    output.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+                },
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("invalid escape"));
+                },
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, QueryError> {
+        let digits = match self.input.get(self.pos..self.pos + 4) {
+            Some(value) => value,
+            None => return Err(self.error("expected 4 hex digits")),
+        };
+        let digits = match std::str::from_utf8(digits) {
+            Ok(value) => value,
+            Err(_) => return Err(self.error("expected 4 hex digits")),
+        };
+        match u32::from_str_radix(digits, 16) {
+            Ok(value) => {
+                self.pos += 4;
+                Ok(value)
+            },
+            Err(_) => Err(self.error("expected 4 hex digits")),
+        }
+    }
+
+    fn filter_or(&mut self) -> Result<Filter, QueryError> {
+        let mut filters = vec![self.filter_and()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'|') || self.peek_at(1) != Some(b'|') {
+                break;
+            }
+            self.pos += 2;
+            self.skip_whitespace();
+            filters.push(self.filter_and()?);
+        }
+        Ok(match filters.len() {
+            1 => filters.pop().unwrap(),
+            _ => Filter::Or(filters),
+        })
+    }
+
+    fn filter_and(&mut self) -> Result<Filter, QueryError> {
+        let mut filters = vec![self.filter_basic()?];
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'&') || self.peek_at(1) != Some(b'&') {
+                break;
+            }
+            self.pos += 2;
+            self.skip_whitespace();
+            filters.push(self.filter_basic()?);
+        }
+        Ok(match filters.len() {
+            1 => filters.pop().unwrap(),
+            _ => Filter::And(filters),
+        })
+    }
+
+    fn filter_basic(&mut self) -> Result<Filter, QueryError> {
+        match self.peek() {
+            Some(b'!') if self.peek_at(1) != Some(b'=') => {
+                self.enter_filter()?;
+                self.pos += 1;
+                self.skip_whitespace();
+                let inner = self.filter_basic()?;
+                self.depth -= 1;
+                Ok(Filter::Not(Box::new(inner)))
+            },
+            Some(b'(') => {
+                self.enter_filter()?;
+                self.pos += 1;
+                self.skip_whitespace();
+                let inner = self.filter_or()?;
+                self.skip_whitespace();
+                self.expect(b')', "expected ')'")?;
+                self.depth -= 1;
+                Ok(inner)
+            },
+            _ => {
+                let left = self.operand()?;
+                self.skip_whitespace();
+                let comparison = match self.comparison() {
+                    Some(value) => value,
+                    None => return match left {
+                        Operand::Path(path) => Ok(Filter::Exists(path)),
+                        Operand::Literal(_) => Err(self.error("expected a comparison")),
+                    },
+                };
+                self.skip_whitespace();
+                let right = self.operand()?;
+                Ok(Filter::Compare(left, comparison, right))
+            },
+        }
+    }
+
+    fn enter_filter(&mut self) -> Result<(), QueryError> {
+        if self.depth == MAX_FILTER_DEPTH {
+            return Err(self.error("filter nested too deeply"));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn comparison(&mut self) -> Option<Comparison> {
+        let (comparison, len) = match (self.peek(), self.peek_at(1)) {
+            (Some(b'='), Some(b'=')) => (Comparison::Eq, 2),
+            (Some(b'!'), Some(b'=')) => (Comparison::Ne, 2),
+            (Some(b'<'), Some(b'=')) => (Comparison::Le, 2),
+            (Some(b'>'), Some(b'=')) => (Comparison::Ge, 2),
+            (Some(b'<'), _) => (Comparison::Lt, 1),
+            (Some(b'>'), _) => (Comparison::Gt, 1),
+            _ => return None,
+        };
+        self.pos += len;
+        Some(comparison)
+    }
+
+    fn operand(&mut self) -> Result<Operand, QueryError> {
+        match self.peek() {
+            Some(b'@') | Some(b'$') => Ok(Operand::Path(self.path()?)),
+            Some(b'\'') | Some(b'"') => {
+                Ok(Operand::Literal(Literal::String(self.string()?)))
+            },
+            Some(b'-') | Some(b'0'..=b'9') => {
+                Ok(Operand::Literal(Literal::Number(self.number()?)))
+            },
+            _ => {
+                let start = self.pos;
+                let literal = match self.name() {
+                    Ok(ref name) if name == b"true" => Literal::Bool(true),
+                    Ok(ref name) if name == b"false" => Literal::Bool(false),
+                    Ok(ref name) if name == b"null" => Literal::Null,
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error("expected a path or a literal"));
+                    },
+                };
+                Ok(Operand::Literal(literal))
+            },
+        }
+    }
+
+    /// Parse a number literal using the JSON number syntax.
+    fn number(&mut self) -> Result<f64, QueryError> {
+        let start = self.pos;
+        while let Some(b'-') | Some(b'+') | Some(b'.') | Some(b'e') | Some(b'E')
+            | Some(b'0'..=b'9') = self.peek()
+        {
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+        match text.parse() {
+            Ok(value) => Ok(value),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            },
+        }
+    }
+
+    /// Parse a path in a filter starting with `@` or `$`.
+    fn path(&mut self) -> Result<Path, QueryError> {
+        let from_root = self.input[self.pos] == b'$';
+        self.pos += 1;
+        let mut steps = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'.') => {
+                    self.pos += 1;
+                    steps.push(Step::Name(self.name()?));
+                },
+                Some(b'[') => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    match self.peek() {
+                        Some(b'\'') | Some(b'"') => steps.push(Step::Name(self.string()?)),
+                        _ => match self.optional_integer()? {
+                            Some(index) => steps.push(Step::Index(index)),
+                            None => return Err(self.error("expected a name or an index")),
+                        },
+                    }
+                    self.skip_whitespace();
+                    self.expect(b']', "expected ']'")?;
+                },
+                _ => return Ok(Path { from_root, steps }),
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use document::Document;
+
+    const ROOMS: &[u8] = br#"{"rooms": [
+        {"id": "!a", "events": [
+            {"type": "m.room.message", "content": {"body": "hello"}},
+            {"type": "m.room.member", "content": {"membership": "join"}},
+            {"type": "m.room.message", "content": {"body": "world"}}
+        ]},
+        {"id": "!b", "events": [
+            {"type": "m.room.message", "content": {"body": "again"}, "depth": 3}
+        ]}
+    ]}"#;
+
+    fn query(input: &[u8], expression: &str) -> Vec<String> {
+        let document = Document::from_slice(input).unwrap();
+        let query = Query::compile(expression).unwrap();
+        query.select(document.root().unwrap()).iter().map(|value| {
+            String::from_utf8(value.raw_bytes().to_vec()).unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn query_children() {
+        assert_eq!(vec![r#""!a""#, r#""!b""#], query(ROOMS, "$.rooms[*].id"));
+        assert_eq!(vec![r#""!b""#], query(ROOMS, "$['rooms'][1]['id']"));
+        assert_eq!(vec![r#""!b""#], query(ROOMS, "$.rooms[-1].id"));
+        assert!(query(ROOMS, "$.rooms[2].id").is_empty());
+        assert!(query(ROOMS, "$.missing").is_empty());
+    }
+
+    #[test]
+    fn query_filter() {
+        assert_eq!(
+            vec![r#""hello""#, r#""world""#, r#""again""#],
+            query(ROOMS, "$.rooms[*].events[?(@.type=='m.room.message')].content.body")
+        );
+        assert_eq!(
+            vec![r#""join""#],
+            query(ROOMS, "$..events[?(@.type != \"m.room.message\")].content.membership")
+        );
+        assert_eq!(
+            vec![r#""again""#],
+            query(ROOMS, "$..events[?@.depth >= 3 && @.type == 'm.room.message'].content.body")
+        );
+        assert_eq!(
+            vec![r#""again""#],
+            query(ROOMS, "$..events[?(@.depth)].content.body")
+        );
+        assert_eq!(
+            vec![r#""hello""#, r#""world""#],
+            query(ROOMS, "$.rooms[0].events[?(!(@.content.membership))].content.body")
+        );
+    }
+
+    #[test]
+    fn query_filter_comparisons() {
+        let input = br#"[1, 2.5, "a", "bA", true, null, [1], {"a": 1}]"#;
+        assert_eq!(vec!["1", "2.5"], query(input, "$[?@ < 3]"));
+        assert_eq!(vec!["2.5"], query(input, "$[?@ > 1 && @ <= 2.5]"));
+        assert_eq!(vec![r#""bA""#], query(input, "$[?@ == 'bA']"));
+        assert_eq!(vec![r#""a""#], query(input, "$[?@ < 'b']"));
+        assert_eq!(vec!["true"], query(input, "$[?@ == true]"));
+        assert_eq!(vec!["null"], query(input, "$[?@ == null]"));
+        assert_eq!(vec![r#"{"a":1}"#], query(input, "$[?@.a == $[0]]"));
+        assert_eq!(vec!["1", "2.5"], query(input, "$[?@ == 1 || @ == 2.5e0]"));
+    }
+
+    #[test]
+    fn query_descendants() {
+        let input = br#"{"a": {"b": 1, "c": {"b": 2}}, "b": [3, {"b": 4}]}"#;
+        assert_eq!(vec!["1", "2", r#"[3,{"b":4}]"#, "4"], query(input, "$..b"));
+        assert_eq!(vec!["3"], query(input, "$..[0]"));
+        assert_eq!(8, query(input, "$..*").len());
+        assert_eq!(vec!["1", "2"], query(input, "$.a..b"));
+    }
+
+    #[test]
+    fn query_slices_and_unions() {
+        let input = b"[0, 1, 2, 3, 4, 5, 6]";
+        assert_eq!(vec!["1", "2"], query(input, "$[1:3]"));
+        assert_eq!(vec!["5", "6"], query(input, "$[-2:]"));
+        assert_eq!(vec!["0", "2", "4", "6"], query(input, "$[::2]"));
+        assert_eq!(vec!["0", "1", "2", "3", "4", "5", "6"], query(input, "$[::-1]"));
+        assert!(query(input, "$[::0]").is_empty());
+        assert_eq!(vec!["0", "3", "5"], query(input, "$[5, 0, 3, 0]"));
+        assert_eq!(vec!["0", "1", "6"], query(input, "$[:2, -1]"));
+        assert_eq!(vec!["1"], query(input, "$[1:5:9223372036854775807]"));
+        assert_eq!(vec!["5"], query(input, "$[5:1:-9223372036854775807]"));
+    }
+
+    #[test]
+    fn query_indices() {
+        let document = Document::from_slice(br#"{"a": [10, 20], "b": 30}"#).unwrap();
+        let query = Query::compile("$..*").unwrap();
+        assert_eq!(vec![2, 3, 4, 6], query.select_indices(document.root().unwrap()));
+    }
+
+    #[test]
+    fn query_compile_errors() {
+        assert_eq!(0, Query::compile("a").unwrap_err().offset);
+        assert_eq!(2, Query::compile("$.").unwrap_err().offset);
+        assert_eq!(2, Query::compile("$[").unwrap_err().offset);
+        assert_eq!(4, Query::compile("$['a").unwrap_err().offset);
+        assert_eq!(4, Query::compile("$[1 2]").unwrap_err().offset);
+        assert_eq!(11, Query::compile("$[?(@.a == )]").unwrap_err().offset);
+        assert!(Query::compile("$[?(@.a == 1]").is_err());
+        assert!(Query::compile("$[?('a')]").is_err());
+        assert!(Query::compile("$ .a").is_ok());
+    }
+
+    #[test]
+    fn query_surrogate_escapes() {
+        let escape = |s: &str| s.replace('|', "\\");
+        let document = Document::from_slice("{\"\u{1F600}\": 1}".as_bytes()).unwrap();
+        let query = Query::compile(&escape("$['|uD83D|uDE00']")).unwrap();
+        assert_eq!(1, query.select_indices(document.root().unwrap()).len());
+        // A high surrogate followed by an escape that isn't a low surrogate.
+        let error = Query::compile(&escape("$['|uD83D|u0041']")).unwrap_err();
+        assert_eq!((4, "invalid unicode escape"), (error.offset, error.message));
+        // A low surrogate on its own.
+        let error = Query::compile(&escape("$['a|uDE00']")).unwrap_err();
+        assert_eq!((5, "invalid unicode escape"), (error.offset, error.message));
+    }
+
+    #[test]
+    fn query_filter_depth() {
+        let nested = format!("$[?{}@.a{}]", "(".repeat(64), ")".repeat(64));
+        assert!(Query::compile(&nested).is_ok());
+        let nested = format!("$[?{}@.a{}]", "(".repeat(200000), ")".repeat(200000));
+        let error = Query::compile(&nested).unwrap_err();
+        assert_eq!((67, "filter nested too deeply"), (error.offset, error.message));
+        let error = Query::compile(&format!("$[?{}@.a]", "!".repeat(200000))).unwrap_err();
+        assert_eq!("filter nested too deeply", error.message);
+        // Long chains of `&&` and `||` don't nest.
+        let chain = vec!["@.a"; 200000].join(" && ");
+        assert!(Query::compile(&format!("$[?{}]", chain)).is_ok());
+    }
+}
@@ -39,6 +39,13 @@ pub fn read_hexdigits(h0: u8, h1: u8, h2: u8, h3: u8) -> u32 {
     hex & 0xFFFF
 }
 
+/// Check that all 4 digits are in the range [0-9A-Fa-f] so that
+/// `read_hexdigits` will decode them correctly.
+pub fn is_hexdigits(h0: u8, h1: u8, h2: u8, h3: u8) -> bool {
+    h0.is_ascii_hexdigit() && h1.is_ascii_hexdigit()
+        && h2.is_ascii_hexdigit() && h3.is_ascii_hexdigit()
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -65,4 +72,11 @@ mod tests {
         assert_eq!(0x89AB, read_hexdigit_4(b"89ab", 0));
         assert_eq!(0xCDEF, read_hexdigit_4(b"cdef", 0));
     }
+
+    #[test]
+    fn readhex_invalid() {
+        assert!(is_hexdigits(b'0', b'a', b'F', b'9'));
+        assert!(!is_hexdigits(b'0', b'0', b'0', b'G'));
+        assert!(!is_hexdigits(b'"', b'0', b'0', b'0'));
+    }
 }
@@ -1,12 +1,24 @@
+use readhex::read_hexdigits;
 use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::slice;
+use std::str;
 
 
+/// Decode the escapes in the contents of a JSON string. The input should not
+/// include the surrounding quotes. Returns `None` if the input contains an
+/// invalid escape. The output is not checked to be valid UTF-8, see
+/// `unescape` for that.
 pub fn unescape_bytes<'a>(input: &'a [u8]) -> Option<Cow<'a, [u8]>> {
     if let Some(pos) = input.iter().position(|c| *c == b'\\') {
         let mut output = Vec::with_capacity(input.len() * 2);
         output.extend_from_slice(&input[..pos]);
 
-        let mut iter = input.iter().skip(pos);
+        let mut iter = input[pos..].iter();
         loop {
             let c = match iter.next() {
                 Some(value) => *value,
@@ -18,31 +30,22 @@ pub fn unescape_bytes<'a>(input: &'a [u8]) -> Option<Cow<'a, [u8]>> {
                     None => return None,
                 };
                 match escaped {
-                    b'"' | b'\\' => output.push(escaped),
+                    b'"' | b'\\' | b'/' => output.push(escaped),
                     b'b' => output.push(0x08),
                     b'f' => output.push(0x0C),
                     b'n' => output.push(0x0A),
                     b'r' => output.push(0x0D),
                     b't' => output.push(0x09),
                     b'u' => {
-                        // Skip the first two digits since they are zero.
-                        if iter.next() == None || iter.next() == None {
-                            return None
-                        }
-                        let h2 = match iter.next() {
-                            Some(value) => *value,
-                            None => return None,
+                        let rest = iter.as_slice();
+                        let (codepoint, len) = match unicode_escape(rest, 0) {
+                            Ok(value) => value,
+                            Err(_) => return None,
                         };
-                        let h3 = match iter.next() {
-                            Some(value) => *value,
-                            None => return None,
-                        };
-                        let value = ((h2 - b'0') << 4) + ((h3 - b'0') & 0x1F);
-                        if h3 > b'9' {
-                            output.push(value - 7);
-                        } else {
-                            output.push(value);
-                        }
+                        let mut buffer = [0; 4];
+                        let encoded = encode_utf8(codepoint, &mut buffer);
+                        output.extend_from_slice(&buffer[..encoded]);
+                        iter = rest[len..].iter();
                     },
                     _ => return None,
                 }
@@ -57,6 +60,215 @@ pub fn unescape_bytes<'a>(input: &'a [u8]) -> Option<Cow<'a, [u8]>> {
     }
 }
 
+/// The reason the contents of a JSON string couldn't be decoded, and the
+/// offset in the input where the problem starts.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum UnescapeError {
+    /// The input ended in the middle of an escape.
+    TruncatedEscape(usize),
+    /// A `\` was followed by a character that isn't a JSON escape.
+    InvalidEscape(usize),
+    /// A `\u` wasn't followed by 4 hex digits.
+    InvalidHex(usize),
+    /// A `\u` escape for a UTF-16 surrogate wasn't part of a valid pair.
+    LoneSurrogate(usize),
+    /// The input wasn't valid UTF-8.
+    InvalidUtf8(usize),
+}
+
+impl UnescapeError {
+    /// The offset in the input where the problem starts.
+    pub fn offset(&self) -> usize {
+        match *self {
+            UnescapeError::TruncatedEscape(offset) => offset,
+            UnescapeError::InvalidEscape(offset) => offset,
+            UnescapeError::InvalidHex(offset) => offset,
+            UnescapeError::LoneSurrogate(offset) => offset,
+            UnescapeError::InvalidUtf8(offset) => offset,
+        }
+    }
+}
+
+impl fmt::Display for UnescapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let message = match *self {
+            UnescapeError::TruncatedEscape(_) => "truncated escape",
+            UnescapeError::InvalidEscape(_) => "invalid escape",
+            UnescapeError::InvalidHex(_) => "invalid hex digits in unicode escape",
+            UnescapeError::LoneSurrogate(_) => "unpaired surrogate in unicode escape",
+            UnescapeError::InvalidUtf8(_) => "invalid UTF-8",
+        };
+        write!(f, "{} at offset {}", message, self.offset())
+    }
+}
+
+impl error::Error for UnescapeError {}
+
+/// Decode the contents of a JSON string into UTF-8. The input should not
+/// include the surrounding quotes.
+///
+/// Every escape is decoded, including `\uXXXX` escapes outside the ASCII
+/// range and UTF-16 surrogate pairs, so this works on JSON which hasn't been
+/// compacted. If the input doesn't contain any escapes then it is returned
+/// without copying.
+pub fn unescape(input: &[u8]) -> Result<Cow<'_, str>, UnescapeError> {
+    let text = utf8(input)?;
+    if !input.contains(&b'\\') {
+        return Ok(Cow::Borrowed(text));
+    }
+    let mut output = String::with_capacity(input.len());
+    unescape_str(text, &mut output)?;
+    Ok(Cow::Owned(output))
+}
+
+/// Decode the contents of a JSON string like `unescape`, appending the
+/// decoded string to the output. Returns the range of the output that the
+/// string was written to. If decoding fails then the output is left as it
+/// was.
+///
+/// Reusing the same output for many strings avoids allocating a new buffer
+/// for each one. See `StringDecoder`.
+pub fn unescape_into(
+    input: &[u8], output: &mut String
+) -> Result<Range<usize>, UnescapeError> {
+    let start = output.len();
+    let result = utf8(input).and_then(|text| unescape_str(text, output));
+    match result {
+        Ok(()) => Ok(start..output.len()),
+        Err(error) => {
+            output.truncate(start);
+            Err(error)
+        },
+    }
+}
+
+fn utf8(input: &[u8]) -> Result<&str, UnescapeError> {
+    str::from_utf8(input).map_err(|error| UnescapeError::InvalidUtf8(error.valid_up_to()))
+}
+
+/// Decode the escapes in a string which has already been checked to be
+/// valid UTF-8, appending the result to the output.
+fn unescape_str(text: &str, output: &mut String) -> Result<(), UnescapeError> {
+    let input = text.as_bytes();
+    let mut pos = 0;
+    while pos < input.len() {
+        // Copy everything up to the next backslash. Backslashes are ASCII so
+        // they are always on a character boundary.
+        let end = match input[pos..].iter().position(|c| *c == b'\\') {
+            Some(value) => pos + value,
+            None => input.len(),
+        };
+        output.push_str(&text[pos..end]);
+        if end == input.len() {
+            break;
+        }
+        let escaped = match input.get(end + 1) {
+            Some(value) => *value,
+            None => return Err(UnescapeError::TruncatedEscape(end)),
+        };
+        pos = end + 2;
+        match escaped {
+            b'"' | b'\\' | b'/' => output.push(escaped as char),
+            b'b' => output.push('\x08'),
+            b'f' => output.push('\x0C'),
+            b'n' => output.push('\n'),
+            b'r' => output.push('\r'),
+            b't' => output.push('\t'),
+            b'u' => {
+                let (codepoint, len) = unicode_escape(&input[pos..], end)?;
+                // Surrogates have been paired up so this is a valid char.
+                output.extend(char::from_u32(codepoint));
+                pos += len;
+            },
+            _ => return Err(UnescapeError::InvalidEscape(end)),
+        }
+    }
+    Ok(())
+}
+
+/// Decodes many JSON strings into a single reusable buffer.
+///
+/// Each decoded string is appended to the buffer and identified by its
+/// range in the buffer. Clearing the decoder keeps the buffer's allocation,
+/// so decoding every string in a stream of documents needn't allocate once
+/// the buffer is large enough, in the same way as reusing a `parse::Stack`.
+pub struct StringDecoder {
+    buffer: String,
+}
+
+impl StringDecoder {
+    /// Create a decoder with an empty buffer.
+    pub fn new() -> StringDecoder {
+        StringDecoder { buffer: String::new() }
+    }
+
+    /// Decode the contents of a JSON string, without the surrounding quotes,
+    /// into the buffer. Returns the range of the buffer holding the string.
+    pub fn decode(&mut self, escaped: &[u8]) -> Result<Range<usize>, UnescapeError> {
+        unescape_into(escaped, &mut self.buffer)
+    }
+
+    /// Decode the contents of a JSON string into the buffer and return the
+    /// decoded string.
+    pub fn decode_str(&mut self, escaped: &[u8]) -> Result<&str, UnescapeError> {
+        let range = self.decode(escaped)?;
+        Ok(&self.buffer[range])
+    }
+
+    /// Get a string that was previously decoded using its range.
+    /// Panics if the range is outside the buffer.
+    pub fn get(&self, range: Range<usize>) -> &str {
+        &self.buffer[range]
+    }
+
+    /// Every string decoded since the decoder was last cleared.
+    pub fn as_str(&self) -> &str {
+        &self.buffer
+    }
+
+    /// Remove the decoded strings, keeping the buffer's allocation.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+}
+
+impl Default for StringDecoder {
+    fn default() -> StringDecoder {
+        StringDecoder::new()
+    }
+}
+
+/// Decode the hex digits of a `\u` escape, combining UTF-16 surrogate
+/// pairs. The input starts after the `\u` and `start` is the offset of the
+/// `\` for reporting errors. Returns the code point and the number of bytes
+/// of input used.
+fn unicode_escape(input: &[u8], start: usize) -> Result<(u32, usize), UnescapeError> {
+    if input.len() < 4 {
+        return Err(UnescapeError::TruncatedEscape(start));
+    }
+    let escaped = read_escape_hex(input).ok_or(UnescapeError::InvalidHex(start))?;
+    if !(0xD800..0xE000).contains(&escaped) {
+        return Ok((escaped, 4));
+    }
+    if escaped >= 0xDC00 {
+        // The second half of a pair without a first half.
+        return Err(UnescapeError::LoneSurrogate(start));
+    }
+    let rest = &input[4..];
+    if rest.len() < 2 || rest[0] != b'\\' || rest[1] != b'u' {
+        return Err(UnescapeError::LoneSurrogate(start));
+    }
+    if rest.len() < 6 {
+        return Err(UnescapeError::TruncatedEscape(start + 6));
+    }
+    let surrogate = read_escape_hex(&rest[2..])
+        .ok_or(UnescapeError::InvalidHex(start + 6))?;
+    if !(0xDC00..0xE000).contains(&surrogate) {
+        return Err(UnescapeError::LoneSurrogate(start));
+    }
+    Ok((0x10000 + (((escaped & 0x3FF) << 10) | (surrogate & 0x3FF)), 10))
+}
+
 const HEX : [u8 ; 16] = *b"0123456789ABCDEF";
 
 pub fn escape_bytes<'a>(input: &'a [u8]) -> Cow<'a, [u8]> {
@@ -93,6 +305,249 @@ pub fn escape_bytes<'a>(input: &'a [u8]) -> Cow<'a, [u8]> {
 }
 
 
+/// Compare the contents of an escaped JSON string with a plain unescaped
+/// string without allocating. The escaped string should not include the
+/// surrounding quotes. Escapes are decoded as they are compared, so
+/// `br#"\u0041\n"#` matches `b"A\n"`.
+pub fn unescaped_eq(escaped: &[u8], plain: &[u8]) -> bool {
+    if plain.len() > escaped.len() {
+        // Decoding never makes a string longer.
+        return false;
+    }
+    if !escaped.contains(&b'\\') {
+        // There aren't any escapes so we can compare the bytes directly.
+        return escaped == plain;
+    }
+    Unescaped::new(escaped).eq(plain.iter().cloned())
+}
+
+
+/// Compare the contents of two escaped JSON strings. The strings are equal if
+/// they are the same once their escapes are decoded, so `br#"\u0041"#` is
+/// equal to `b"A"`. The escapes are decoded as the strings are compared
+/// without allocating.
+pub fn escaped_eq(a: &[u8], b: &[u8]) -> bool {
+    if !a.contains(&b'\\') && !b.contains(&b'\\') {
+        return a == b;
+    }
+    Unescaped::new(a).eq(Unescaped::new(b))
+}
+
+/// Order the contents of two escaped JSON strings by the code points of
+/// their decoded contents. Comparing UTF-8 byte by byte gives the same order
+/// as comparing code points, so this is the same order as comparing the
+/// decoded strings as `str`.
+pub fn escaped_cmp(a: &[u8], b: &[u8]) -> Ordering {
+    if !a.contains(&b'\\') && !b.contains(&b'\\') {
+        return a.cmp(b);
+    }
+    Unescaped::new(a).cmp(Unescaped::new(b))
+}
+
+/// The escaped contents of a JSON string, without the surrounding quotes.
+/// Equality, ordering and hashing use the decoded contents of the string,
+/// so differently escaped copies of the same string can be used to
+/// deduplicate values in a `HashSet` or a `BTreeSet`.
+#[derive(Clone, Copy, Debug)]
+pub struct JsonStr<'a> {
+    escaped: &'a [u8],
+}
+
+impl<'a> JsonStr<'a> {
+    /// Wrap the escaped contents of a JSON string.
+    pub fn new(escaped: &'a [u8]) -> JsonStr<'a> {
+        JsonStr { escaped }
+    }
+
+    /// The escaped contents of the string.
+    pub fn escaped(&self) -> &'a [u8] {
+        self.escaped
+    }
+}
+
+impl<'a> PartialEq for JsonStr<'a> {
+    fn eq(&self, other: &JsonStr) -> bool {
+        escaped_eq(self.escaped, other.escaped)
+    }
+}
+
+impl<'a> Eq for JsonStr<'a> {}
+
+impl<'a> PartialOrd for JsonStr<'a> {
+    fn partial_cmp(&self, other: &JsonStr<'a>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<'a> Ord for JsonStr<'a> {
+    fn cmp(&self, other: &JsonStr<'a>) -> Ordering {
+        escaped_cmp(self.escaped, other.escaped)
+    }
+}
+
+/// The decoded bytes are fed to the hasher in fixed size chunks so that
+/// strings without escapes can skip decoding and still hash the same as
+/// escaped copies of themselves.
+const HASH_CHUNK: usize = 64;
+
+impl<'a> Hash for JsonStr<'a> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if !self.escaped.contains(&b'\\') {
+            for chunk in self.escaped.chunks(HASH_CHUNK) {
+                state.write(chunk);
+            }
+        } else {
+            let mut buffer = [0; HASH_CHUNK];
+            let mut len = 0;
+            for c in Unescaped::new(self.escaped) {
+                buffer[len] = c;
+                len += 1;
+                if len == HASH_CHUNK {
+                    state.write(&buffer);
+                    len = 0;
+                }
+            }
+            if len > 0 {
+                state.write(&buffer[..len]);
+            }
+        }
+        // Terminate the string in the same way as `str` so that hashing a
+        // sequence of strings is unambiguous.
+        state.write_u8(0xFF);
+    }
+}
+
+/// Iterates over the UTF-8 bytes of an escaped JSON string, decoding escapes
+/// as it goes.
+///
+/// Malformed escapes are passed through unchanged and lone surrogates are
+/// encoded as if they were ordinary code points. This means that every
+/// input decodes to something, so decoded strings can be compared and
+/// hashed consistently even if they aren't valid JSON.
+struct Unescaped<'a> {
+    iter: slice::Iter<'a, u8>,
+    pending: [u8; 4],
+    pending_start: usize,
+    pending_end: usize,
+}
+
+impl<'a> Unescaped<'a> {
+    fn new(input: &'a [u8]) -> Unescaped<'a> {
+        Unescaped {
+            iter: input.iter(),
+            pending: [0; 4],
+            pending_start: 0,
+            pending_end: 0,
+        }
+    }
+
+    /// Decode a `\uXXXX` escape. The iterator is positioned after the `u`.
+    /// Returns `None` if the escape doesn't have 4 hex digits.
+    fn unicode_escape(&mut self) -> Option<u32> {
+        let escaped = read_escape_hex(self.iter.as_slice())?;
+        self.iter.nth(3);
+        if !(0xD800..0xDC00).contains(&escaped) {
+            return Some(escaped);
+        }
+        // This is the first half of a surrogate pair, check if the second
+        // half follows it.
+        let rest = self.iter.as_slice();
+        if rest.len() < 6 || rest[0] != b'\\' || rest[1] != b'u' {
+            return Some(escaped);
+        }
+        match read_escape_hex(&rest[2..]) {
+            Some(surrogate) if (0xDC00..0xE000).contains(&surrogate) => {
+                self.iter.nth(5);
+                Some(0x10000 + (((escaped & 0x3FF) << 10) | (surrogate & 0x3FF)))
+            },
+            _ => Some(escaped),
+        }
+    }
+}
+
+impl<'a> Iterator for Unescaped<'a> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.pending_start < self.pending_end {
+            self.pending_start += 1;
+            return Some(self.pending[self.pending_start - 1]);
+        }
+        let c = *self.iter.next()?;
+        if c != b'\\' {
+            return Some(c);
+        }
+        let escaped = match self.iter.as_slice().first() {
+            Some(value) => *value,
+            None => return Some(c),
+        };
+        let decoded = match escaped {
+            b'"' | b'\\' | b'/' => escaped,
+            b'b' => 0x08,
+            b'f' => 0x0C,
+            b'n' => 0x0A,
+            b'r' => 0x0D,
+            b't' => 0x09,
+            b'u' => {
+                self.iter.next();
+                let codepoint = match self.unicode_escape() {
+                    Some(value) => value,
+                    None => {
+                        // Pass the malformed escape through unchanged.
+                        self.pending[0] = b'u';
+                        self.pending_start = 0;
+                        self.pending_end = 1;
+                        return Some(c);
+                    },
+                };
+                let len = encode_utf8(codepoint, &mut self.pending);
+                self.pending_start = 1;
+                self.pending_end = len;
+                return Some(self.pending[0]);
+            },
+            // Pass the malformed escape through unchanged.
+            _ => return Some(c),
+        };
+        self.iter.next();
+        Some(decoded)
+    }
+}
+
+/// Read the 4 hex digits at the start of the input. Returns `None` if there
+/// aren't 4 valid hex digits.
+fn read_escape_hex(input: &[u8]) -> Option<u32> {
+    if input.len() < 4 || !input[..4].iter().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(read_hexdigits(input[0], input[1], input[2], input[3]))
+}
+
+/// Encode a code point as UTF-8. Surrogates are encoded as 3 bytes like any
+/// other code point in the basic multilingual plane. Returns the number of
+/// bytes written.
+fn encode_utf8(codepoint: u32, output: &mut [u8; 4]) -> usize {
+    if codepoint < 0x80 {
+        output[0] = codepoint as u8;
+        1
+    } else if codepoint < 0x800 {
+        output[0] = (codepoint >> 6) as u8 | 0xC0;
+        output[1] = (codepoint as u8 & 0x3F) | 0x80;
+        2
+    } else if codepoint < 0x10000 {
+        output[0] = (codepoint >> 12) as u8 | 0xE0;
+        output[1] = ((codepoint >> 6) & 0x3F) as u8 | 0x80;
+        output[2] = (codepoint as u8 & 0x3F) | 0x80;
+        3
+    } else {
+        output[0] = (codepoint >> 18) as u8 | 0xF0;
+        output[1] = ((codepoint >> 12) & 0x3F) as u8 | 0x80;
+        output[2] = ((codepoint >> 6) & 0x3F) as u8 | 0x80;
+        output[3] = (codepoint as u8 & 0x3F) | 0x80;
+        4
+    }
+}
+
+
 #[cfg(all(feature = "quickcheck_test", test))]
 mod quickcheck_test {
     use super::*;
@@ -223,4 +678,142 @@ mod tests {
         assert!(unescape_bytes(br#"\u00"#).is_none());
         assert!(unescape_bytes(br#"\u000"#).is_none());
     }
+
+    #[test]
+    fn unescaped_eq_plain() {
+        assert!(unescaped_eq(b"content", b"content"));
+        assert!(!unescaped_eq(b"content", b"contents"));
+        assert!(!unescaped_eq(b"contents", b"content"));
+        assert!(unescaped_eq(b"", b""));
+    }
+
+    #[test]
+    fn unescaped_eq_escapes() {
+        assert!(unescaped_eq(br#"a\"b\\c\/d"#, b"a\"b\\c/d"));
+        assert!(unescaped_eq(br#"\b\f\n\r\t"#, b"\x08\x0C\n\r\t"));
+        assert!(unescaped_eq(br#"\u0001x"#, b"\x01x"));
+        assert!(unescaped_eq(br#"\u0041\u00e9\u263A"#, "A\u{e9}\u{263A}".as_bytes()));
+        assert!(unescaped_eq(br#"\uD83D\uDE00"#, "\u{1F600}".as_bytes()));
+        assert!(!unescaped_eq(br#"\n"#, b"n"));
+        assert!(!unescaped_eq(br#"\u0041"#, b"B"));
+        assert!(!unescaped_eq(br#"\u0041"#, b"AA"));
+    }
+
+    #[test]
+    fn unescaped_eq_malformed() {
+        assert!(unescaped_eq(br#"\g"#, br#"\g"#));
+        assert!(unescaped_eq(br#"\u00"#, br#"\u00"#));
+        assert!(unescaped_eq(br#"\"#, br#"\"#));
+        assert!(!unescaped_eq(br#"\uD83D"#, "\u{1F600}".as_bytes()));
+    }
+
+    #[test]
+    fn unescape_bytes_unicode() {
+        assert_eq!(
+            "A\u{e9}\u{263A}\u{1F600}/".as_bytes(),
+            &unescape_bytes(br#"\u0041\u00E9\u263a\uD83D\uDE00\/"#).unwrap()[..]
+        );
+        assert!(unescape_bytes(br#"\uD83D"#).is_none());
+        assert!(unescape_bytes(br#"\uDE00"#).is_none());
+        assert!(unescape_bytes(br#"\u00zz"#).is_none());
+    }
+
+    #[test]
+    fn unescape_str() {
+        assert_eq!(Ok(Cow::Borrowed("plain \u{263A}")), unescape("plain \u{263A}".as_bytes()));
+        assert_eq!(
+            Ok(Cow::Owned("\u{263A} \"\\/\x08\x0C\n\r\t \u{1F600}x".to_string())),
+            unescape(br#"\u263A \"\\\/\b\f\n\r\t \ud83d\ude00x"#)
+        );
+        assert_eq!(Ok(Cow::Owned("\u{0}".to_string())), unescape(br#"\u0000"#));
+    }
+
+    #[test]
+    fn unescape_str_errors() {
+        assert_eq!(Err(UnescapeError::TruncatedEscape(1)), unescape(br#"a\"#));
+        assert_eq!(Err(UnescapeError::TruncatedEscape(1)), unescape(br#"a\u00"#));
+        assert_eq!(Err(UnescapeError::InvalidEscape(2)), unescape(br#"ab\g"#));
+        assert_eq!(Err(UnescapeError::InvalidHex(0)), unescape(br#"\u12G4"#));
+        assert_eq!(Err(UnescapeError::LoneSurrogate(0)), unescape(br#"\uD83Dx"#));
+        assert_eq!(Err(UnescapeError::LoneSurrogate(0)), unescape(br#"\uDE00"#));
+        assert_eq!(Err(UnescapeError::LoneSurrogate(0)), unescape(br#"\uD83D\u0041"#));
+        assert_eq!(Err(UnescapeError::TruncatedEscape(6)), unescape(br#"\uD83D\uDE"#));
+        assert_eq!(Err(UnescapeError::InvalidHex(6)), unescape(br#"\uD83D\uXE00"#));
+        assert_eq!(Err(UnescapeError::InvalidUtf8(1)), unescape(b"a\xFF\\n"));
+    }
+
+    #[test]
+    fn escaped_eq_strings() {
+        assert!(escaped_eq(b"abc", b"abc"));
+        assert!(!escaped_eq(b"abc", b"abd"));
+        assert!(escaped_eq(br#"\u0041\n"#, b"A\\n"));
+        assert!(escaped_eq(br#"\u0041\n"#, br#"A\u000a"#));
+        assert!(escaped_eq(br#"\ud83d\ude00"#, br#"\uD83D\uDE00"#));
+        assert!(escaped_eq(br#"\ud83d\ude00"#, "\u{1F600}".as_bytes()));
+        assert!(escaped_eq(br#"\/"#, b"/"));
+        assert!(!escaped_eq(br#"\u0041"#, br#"\u0042"#));
+        assert!(!escaped_eq(br#"\u0041"#, br#"\u0041A"#));
+    }
+
+    #[test]
+    fn escaped_cmp_strings() {
+        assert_eq!(Ordering::Less, escaped_cmp(b"a", b"b"));
+        assert_eq!(Ordering::Equal, escaped_cmp(br#"\u0061"#, b"a"));
+        assert_eq!(Ordering::Greater, escaped_cmp(br#"\u0062"#, b"a"));
+        assert_eq!(Ordering::Less, escaped_cmp(b"a", br#"a\u0000"#));
+        // U+FFFF is before U+1F600 in code point order, even though the
+        // UTF-16 encoding of U+1F600 starts with 0xD83D.
+        assert_eq!(Ordering::Less, escaped_cmp(br#"\uFFFF"#, br#"\uD83D\uDE00"#));
+        assert_eq!(Ordering::Less, escaped_cmp(br#"\u00e9"#, "\u{263A}".as_bytes()));
+    }
+
+    #[test]
+    fn json_str_hash() {
+        use std::collections::HashSet;
+
+        let long_plain = "x".repeat(200);
+        let long_escaped = "\\u0078".repeat(200);
+        let mut set = HashSet::new();
+        assert!(set.insert(JsonStr::new(b"A\\n")));
+        assert!(!set.insert(JsonStr::new(br#"\u0041\n"#)));
+        assert!(!set.insert(JsonStr::new(br#"A\u000A"#)));
+        assert!(set.insert(JsonStr::new(b"B")));
+        assert!(set.insert(JsonStr::new(long_plain.as_bytes())));
+        assert!(!set.insert(JsonStr::new(long_escaped.as_bytes())));
+        assert_eq!(3, set.len());
+    }
+
+    #[test]
+    fn json_str_ord() {
+        let mut values = [
+            JsonStr::new(br#"\u0063"#), JsonStr::new(b"a"), JsonStr::new(br#"\u0062"#),
+        ];
+        values.sort();
+        let sorted: Vec<&[u8]> = values.iter().map(|value| value.escaped()).collect();
+        assert_eq!(vec![&b"a"[..], &br#"\u0062"#[..], &br#"\u0063"#[..]], sorted);
+    }
+
+    #[test]
+    fn unescape_into_buffer() {
+        let mut output = String::from("x");
+        assert_eq!(Ok(1..3), unescape_into(br#"a\n"#, &mut output));
+        assert_eq!(Ok(3..7), unescape_into(br#"\u263Ab"#, &mut output));
+        assert_eq!(Ok(7..7), unescape_into(b"", &mut output));
+        assert_eq!(Err(UnescapeError::InvalidEscape(1)), unescape_into(br#"c\q"#, &mut output));
+        assert_eq!("xa\n\u{263A}b", output);
+    }
+
+    #[test]
+    fn string_decoder() {
+        let mut decoder = StringDecoder::new();
+        let first = decoder.decode(br#"hello\tworld"#).unwrap();
+        let second = decoder.decode(b"plain").unwrap();
+        assert!(decoder.decode(br#"\uD800"#).is_err());
+        assert_eq!("\u{e9}", decoder.decode_str(br#"\u00e9"#).unwrap());
+        assert_eq!("hello\tworld", decoder.get(first));
+        assert_eq!("plain", decoder.get(second));
+        assert_eq!("hello\tworldplain\u{e9}", decoder.as_str());
+        decoder.clear();
+        assert_eq!("", decoder.as_str());
+    }
 }
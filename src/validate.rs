@@ -1,10 +1,25 @@
+use diagnostic::Diagnostic;
+use error::{Error, ErrorKind};
+use number::parse_i64;
+use parents::ParentMap;
 use parse::Node;
+use pointer::JsonPointer;
+use std::error;
+use std::fmt;
+use std::ops::Range;
+use value::{JsonKind, ValueRef};
+use visit::{walk, Visitor};
 
 struct ValidateStack {
+    index: u32,
     end: u32,
     is_object: bool,
 }
 
+/// A problem and the range of bytes it was found in, relative to the start
+/// of the key or value being checked.
+type Problem = (ErrorKind, Range<usize>);
+
 
 /// Validate that the result of parsing JSON is actually valid JSON.
 ///
@@ -22,17 +37,169 @@ struct ValidateStack {
 /// Compacting the JSON may inaddvertently convert invalid JSON into valid
 /// JSON. This may be unsuitable for applications that are expected to ensure
 /// that their input is valid JSON, rather than just their output.
-pub fn validate(json_bytes: &[u8], json_nodes: &[Node]) -> Result<(),()> {
-    if validate_(json_bytes, json_nodes) {
+///
+/// Fails with the first problem found.
+pub fn validate(json_bytes: &[u8], json_nodes: &[Node]) -> Result<(), ValidationError> {
+    let mut first = None;
+    check(json_bytes, json_nodes, &mut |fault| {
+        first = Some(fault);
+        false
+    });
+    match first {
+        None => Ok(()),
+        Some(fault) => Err(ValidationError::new(json_bytes, json_nodes, fault)),
+    }
+}
+
+/// Validate the JSON like `validate` but carry on after finding a problem,
+/// returning every problem found. The nodes say where each key and value ends
+/// so checking can continue after a bad key or value, or a mismatched bracket.
+/// Checking stops early if the nodes don't match the bytes, for example if a
+/// node with children doesn't start with a bracket. An empty vector means
+/// the JSON is valid.
+pub fn validate_all(json_bytes: &[u8], json_nodes: &[Node]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    check(json_bytes, json_nodes, &mut |fault| {
+        diagnostics.push(Diagnostic::new(fault.kind, fault.range, Some(fault.node)));
+        true
+    });
+    diagnostics
+}
+
+/// Why validation failed and where.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ValidationError {
+    kind: ErrorKind,
+    node: usize,
+    range: Range<usize>,
+    pointer: JsonPointer,
+}
+
+impl ValidationError {
+    fn new(json_bytes: &[u8], json_nodes: &[Node], fault: Fault) -> ValidationError {
+        let pointer = if fault.node < json_nodes.len() {
+            ParentMap::new(json_bytes, json_nodes).path_of(fault.node)
+        } else {
+            None
+        };
+        ValidationError {
+            kind: fault.kind,
+            node: fault.node,
+            range: fault.range,
+            pointer: pointer.unwrap_or_default(),
+        }
+    }
+
+    /// What was wrong.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The index of the node where the problem was found. Problems with
+    /// brackets are reported on the object or array being closed.
+    pub fn node(&self) -> usize {
+        self.node
+    }
+
+    /// The range of bytes in the compacted JSON that were wrong. This is the
+    /// whole of a bad number or literal, but just the escape or character
+    /// that was wrong within a string.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// A JSON Pointer to the node where the problem was found.
+    pub fn pointer(&self) -> &JsonPointer {
+        &self.pointer
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.kind, self.range.start)?;
+        if !self.pointer.tokens().is_empty() {
+            write!(f, " in {}", self.pointer)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ValidationError {}
+
+impl From<ValidationError> for Error {
+    fn from(error: ValidationError) -> Error {
+        Error::new(error.kind, error.range.start)
+    }
+}
+
+/// The largest integer that every JSON implementation can represent exactly,
+/// 2^53 - 1. Integers outside the range `-MAX_SAFE_INTEGER` to
+/// `MAX_SAFE_INTEGER` may be rounded by implementations that store numbers
+/// as IEEE doubles.
+pub const MAX_SAFE_INTEGER: i64 = 9007199254740991;
+
+/// Check that every number in the JSON is an integer in the range
+/// `-MAX_SAFE_INTEGER` to `MAX_SAFE_INTEGER` with no fraction or exponent.
+/// This is required by I-JSON for interoperability and by Matrix canonical
+/// JSON. Fails with the indices of every number node that doesn't conform.
+///
+/// This only checks the numbers. It should be used alongside `validate` if
+/// the rest of the JSON hasn't already been validated.
+pub fn validate_integers(json_bytes: &[u8], json_nodes: &[Node]) -> Result<(), Vec<usize>> {
+    let root = match ValueRef::root(json_bytes, json_nodes) {
+        Some(value) => value,
+        None => return Ok(()),
+    };
+    let mut checker = IntegerChecker { invalid: Vec::new() };
+    walk(root, &mut checker);
+    if checker.invalid.is_empty() {
         Ok(())
     } else {
-        Err(())
+        Err(checker.invalid)
+    }
+}
+
+struct IntegerChecker {
+    invalid: Vec<usize>,
+}
+
+impl<'a> Visitor<'a> for IntegerChecker {
+    fn scalar(&mut self, value: ValueRef<'a>) {
+        if value.kind() != JsonKind::Number {
+            return;
+        }
+        match parse_i64(value.raw_bytes()) {
+            Ok(number) if (-MAX_SAFE_INTEGER..=MAX_SAFE_INTEGER).contains(&number) => {},
+            _ => self.invalid.push(value.index()),
+        }
+    }
+}
+
+/// A problem found by `check`.
+struct Fault {
+    kind: ErrorKind,
+    node: usize,
+    range: Range<usize>,
+}
+
+impl Fault {
+    /// A problem in the key or value at `start`.
+    fn at(start: usize, node: usize, problem: Problem) -> Fault {
+        let (kind, range) = problem;
+        Fault { kind, node, range: start + range.start..start + range.end }
     }
 }
 
-fn validate_(json_bytes: &[u8], json_nodes: &[Node]) -> bool {
+/// Walk the nodes checking the JSON and calling `report` with each problem.
+/// Stops if `report` returns `false`, or if the nodes are too broken to carry
+/// on. The nodes say where each key and value ends so a bad key or value
+/// doesn't stop us checking the ones after it.
+fn check<F: FnMut(Fault) -> bool>(json_bytes: &[u8], json_nodes: &[Node], report: &mut F) {
     if json_nodes.len() == 1 {
-        return validate_empty(json_bytes);
+        if let Err(problem) = validate_empty(json_bytes) {
+            report(Fault::at(0, 0, problem));
+        }
+        return;
     }
     let mut stack: Vec<ValidateStack> = Vec::new();
     let mut expecting_key = false;
@@ -43,11 +210,16 @@ fn validate_(json_bytes: &[u8], json_nodes: &[Node]) -> bool {
         if expecting_key {
             let start = offset;
             offset += node.length_in_bytes as usize;
-            if !validate_key(&json_bytes[start..offset]) {
-                return false;
+            if let Err(problem) = validate_key(&json_bytes[start..offset]) {
+                if !report(Fault::at(start, index, problem)) {
+                    return;
+                }
             }
-            if json_bytes[offset] != b':' {
-                return false;
+            if json_bytes.get(offset) != Some(&b':') {
+                let fault = Fault::at(offset, index, (ErrorKind::ExpectedColon, 0..1));
+                if !report(fault) {
+                    return;
+                }
             }
             offset += 1;
             expecting_key = false;
@@ -55,32 +227,44 @@ fn validate_(json_bytes: &[u8], json_nodes: &[Node]) -> bool {
             is_object = match json_bytes[offset] {
                 b'{' => true,
                 b'[' => false,
-                _ => return false,
+                _ => {
+                    report(Fault::at(offset, index, (ErrorKind::ExpectedContainer, 0..1)));
+                    return;
+                },
             };
             end = index + node.children as usize;
-            stack.push(ValidateStack { end: end as u32, is_object: is_object });
+            stack.push(ValidateStack {
+                index: index as u32, end: end as u32, is_object
+            });
             expecting_key = is_object;
             offset += 1
         } else {
             let start = offset;
             offset += node.length_in_bytes as usize;
-            if !validate_scalar(&json_bytes[start..offset]) {
-                return false;
+            if let Err(problem) = validate_scalar(&json_bytes[start..offset]) {
+                if !report(Fault::at(start, index, problem)) {
+                    return;
+                }
             }
             while index == end {
-                if is_object {
-                    if json_bytes[offset] != b'}' {
-                        return false;
-                    }
-                } else {
-                    if json_bytes[offset] != b']' {
-                        return false;
+                let close = if is_object { b'}' } else { b']' };
+                if json_bytes.get(offset) != Some(&close) {
+                    let container = stack.last().map_or(0, |state| state.index as usize);
+                    let fault = Fault::at(offset, container, (ErrorKind::MismatchedBracket, 0..1));
+                    if !report(fault) {
+                        return;
                     }
                 }
                 offset += 1;
                 let _ = stack.pop();
                 let state = match stack.last() {
-                    None => return true,
+                    None => {
+                        if offset < json_bytes.len() {
+                            let trailing = 0..json_bytes.len() - offset;
+                            report(Fault::at(offset, 0, (ErrorKind::TrailingData, trailing)));
+                        }
+                        return;
+                    },
                     Some(value) => value
                 };
                 end = state.end as usize;
@@ -90,49 +274,52 @@ fn validate_(json_bytes: &[u8], json_nodes: &[Node]) -> bool {
             expecting_key = is_object;
         }
     }
-    false
+    let container = stack.last().map_or(0, |state| state.index as usize);
+    report(Fault::at(offset, container, (ErrorKind::UnexpectedEnd, 0..0)));
 }
 
 /// Validate an empty array or object at the start of the JSON.
 /// The parser assumes that all JSON of length 2 contains a single
 /// empty erray or object. We therefore need to check both bytes
 /// to make sure that was the case.
-fn validate_empty(bytes: &[u8]) -> bool {
-    if bytes.len() != 2 {
-        return false;
-    }
-    let char1 = bytes[0];
-    let char2 = bytes[1];
-    match char1 {
-        b'{' => char2 == b'}',
-        b'[' => char2 == b']',
-        _ => false,
+fn validate_empty(bytes: &[u8]) -> Result<(), Problem> {
+    let close = match bytes.first() {
+        Some(&b'{') => b'}',
+        Some(&b'[') => b']',
+        _ => return Err((ErrorKind::ExpectedContainer, 0..1)),
+    };
+    match bytes.get(1) {
+        None => Err((ErrorKind::UnexpectedEnd, 1..1)),
+        Some(&c) if c != close => Err((ErrorKind::MismatchedBracket, 1..2)),
+        Some(_) if bytes.len() > 2 => Err((ErrorKind::TrailingData, 2..bytes.len())),
+        Some(_) => Ok(()),
     }
 }
 
 /// Validate a JSON scalar value. This may be an empty array, an empty object,
 /// a literal, a string, or a number.
-fn validate_scalar(bytes: &[u8]) -> bool {
-    if validate_empty(bytes) {
+fn validate_scalar(bytes: &[u8]) -> Result<(), Problem> {
+    if bytes == b"{}" || bytes == b"[]" {
         // The bytes were an empty array or an empty object.
-        return true;
+        return Ok(());
     }
-    if bytes.len() == 0 {
+    match bytes.first() {
+        Some(&b'\"') => validate_string(bytes),
+        // Literals must be lower case.
+        Some(&b't') => validate_literal(bytes, b"true"),
+        Some(&b'f') => validate_literal(bytes, b"false"),
+        Some(&b'n') => validate_literal(bytes, b"null"),
+        Some(&b'-') | Some(&(b'0'..=b'9')) => validate_number(bytes),
         // The parser doesn't check if scalar is empty.
-        return false;
+        _ => Err((ErrorKind::InvalidValue, 0..bytes.len())),
     }
-    match bytes[0] {
-        b'\"' => validate_string(bytes),
-        // Literals must be lower case.
-        b't' => bytes == b"true",
-        b'f' => bytes == b"false",
-        b'n' => bytes == b"null",
-        // Numbers start with an optional '-' minus sign followed by either a
-        // '0' or a '1'...'9' followed by zero or more digits.
-        b'-' => validate_negative(&bytes[1..]),
-        b'0' => validate_fraction(&bytes[1..]),
-        b'1' ... b'9' => validate_digits(&bytes[1..]),
-        _ => false,
+}
+
+fn validate_literal(bytes: &[u8], literal: &[u8]) -> Result<(), Problem> {
+    if bytes == literal {
+        Ok(())
+    } else {
+        Err((ErrorKind::InvalidLiteral, 0..bytes.len()))
     }
 }
 
@@ -140,37 +327,34 @@ fn validate_scalar(bytes: &[u8]) -> bool {
 /// Validate a JSON object key. We need to check that it starts with a '"'
 /// since the parser assumes that the character following a '{' or a '.' is
 /// a '"'.
-fn validate_key(bytes: &[u8]) -> bool {
-    if bytes.len() == 0 {
-        return false;
-    }
-    match bytes[0] {
-        b'\"' => validate_string(bytes),
-        _ => false,
+fn validate_key(bytes: &[u8]) -> Result<(), Problem> {
+    match bytes.first() {
+        Some(&b'\"') => validate_string(bytes),
+        _ => Err((ErrorKind::InvalidKey, 0..bytes.len())),
     }
 }
 
 /// Validate a JSON string. The parser checks for the starting and ending '"'.
 /// So we just need to check that there isn't any illegal control characters,
 /// and the escapes are valid.
-fn validate_string(bytes: &[u8]) -> bool {
+fn validate_string(bytes: &[u8]) -> Result<(), Problem> {
     if bytes.len() < 2 {
         // This shouldn't happen since the parser must have found an opening
         // and a closing '"'.
-        return false;
+        return Err((ErrorKind::UnterminatedString, 0..bytes.len()));
     }
-    let mut iter = bytes[1..bytes.len()-1].iter();
+    let mut iter = bytes[..bytes.len()-1].iter().enumerate().skip(1);
     loop {
-        let c = match iter.next() {
-            None => return true,
-            Some(value) => *value,
+        let (position, c) = match iter.next() {
+            None => return Ok(()),
+            Some((position, value)) => (position, *value),
         };
         if c == b'\\' {
             let escaped = match iter.next() {
                 // There should always be another character since the parser
                 // checks for escapes at the end of strings.
-                None => return false,
-                Some(value) => *value,
+                None => return Err((ErrorKind::TruncatedEscape, position..position + 1)),
+                Some((_, value)) => *value,
             };
             match escaped {
                 // We only need to check that the chacacter is valid. We don't
@@ -182,27 +366,44 @@ fn validate_string(bytes: &[u8]) -> bool {
                 // compacted.
                 b'"' | b'\\' => continue,
                 b'b' | b'f' | b'n' | b'r' | b't' | b'u' => continue,
-                _ => return false,
+                b'/' => return Err((ErrorKind::UncompactedEscape, position..position + 2)),
+                _ => return Err((ErrorKind::InvalidEscape, position..position + 2)),
             }
         } else if c < b' ' {
             // Check for control characters less than b' ' == 0x20.
-            return false
+            return Err((ErrorKind::ControlCharacter, position..position + 1))
         }
     }
 }
 
 
-/// Validate a negative number checking the bytes after the '-' sign.
-fn validate_negative(bytes: &[u8]) -> bool {
-    if bytes.len() == 0 {
-        return false;
-    }
-    match bytes[0] {
-        // The '-' must be followed by either a single '0' or '1'...'9'
-        // followed by zero or more digits.
-        b'0' => validate_fraction(&bytes[1..]),
-        b'1' ... b'9' => validate_digits(&bytes[1..]),
+/// Check that the bytes are a JSON number. The `number` module uses this so
+/// that it converts exactly the numbers that validating accepts.
+pub fn is_number(bytes: &[u8]) -> bool {
+    validate_number(bytes).is_ok()
+}
+
+/// Validate a number. Numbers start with an optional '-' minus sign followed
+/// by either a single '0' or a '1'...'9' followed by zero or more digits.
+fn validate_number(bytes: &[u8]) -> Result<(), Problem> {
+    let digits = match bytes.first() {
+        Some(&b'-') => &bytes[1..],
+        _ => bytes,
+    };
+    let valid = match digits.first() {
+        Some(&b'0') => {
+            if digits.get(1).map_or(false, u8::is_ascii_digit) {
+                return Err((ErrorKind::LeadingZero, 0..bytes.len()));
+            }
+            validate_fraction(&digits[1..])
+        },
+        Some(&(b'1'..=b'9')) => validate_digits(&digits[1..]),
         _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err((ErrorKind::InvalidNumber, 0..bytes.len()))
     }
 }
 
@@ -234,6 +435,10 @@ fn validate_fraction(bytes: &[u8]) -> bool {
         return validate_exponent(bytes);
     }
     let digits = &bytes[1..];
+    if !digits[0].is_ascii_digit() {
+        // The fraction must have a digit after the decimal point.
+        return false;
+    }
     for (index, byte) in digits.iter().enumerate() {
         match *byte {
             b'0' ... b'9' => continue,
@@ -279,15 +484,22 @@ fn validate_exponent(bytes: &[u8]) -> bool {
 
 #[cfg(test)]
 mod test {
+    use error::ErrorKind;
+    use std::ops::Range;
 
     fn validate(input: &[u8]) -> bool {
         let mut parsed : Vec<::parse::Node> = Vec::new();
         let mut stack : Vec<::parse::Stack> = Vec::new();
         ::parse::parse(input, &mut parsed, &mut stack).unwrap();
-        match super::validate(input, parsed.as_slice()) {
-            Ok(()) => true,
-            Err(()) => false
-        }
+        super::validate(input, parsed.as_slice()).is_ok()
+    }
+
+    fn validate_error(input: &[u8]) -> (ErrorKind, Range<usize>) {
+        let mut parsed : Vec<::parse::Node> = Vec::new();
+        let mut stack : Vec<::parse::Stack> = Vec::new();
+        ::parse::parse(input, &mut parsed, &mut stack).unwrap();
+        let error = super::validate(input, parsed.as_slice()).unwrap_err();
+        (error.kind(), error.range())
     }
 
     #[test]
@@ -361,6 +573,7 @@ mod test {
     fn validate_fractions() {
         assert_eq!(true, validate(b"[0.0,0.01,0.123456789]"));
         assert_eq!(false, validate(b"[0.]"));
+        assert_eq!(false, validate(b"[0.e1]"));
         assert_eq!(false, validate(b"[0A0]"));
     }
 
@@ -387,4 +600,107 @@ mod test {
     fn validate_nested() {
         assert_eq!(true, validate(br#"[{"":[]},[],{}]"#));
     }
+
+    #[test]
+    fn validate_errors() {
+        assert_eq!((ErrorKind::MismatchedBracket, 1..2), validate_error(b"{]"));
+        assert_eq!((ErrorKind::MismatchedBracket, 4..5), validate_error(b"[[0]}"));
+        assert_eq!((ErrorKind::ExpectedContainer, 0..1), validate_error(b"ab"));
+        assert_eq!((ErrorKind::ExpectedColon, 3..4), validate_error(br#"{""@1}"#));
+        assert_eq!((ErrorKind::InvalidKey, 1..3), validate_error(br#"{@":1}"#));
+        assert_eq!((ErrorKind::InvalidValue, 1..2), validate_error(b"[,]"));
+        assert_eq!((ErrorKind::InvalidValue, 3..4), validate_error(b"[1,@]"));
+        assert_eq!((ErrorKind::InvalidLiteral, 1..5), validate_error(b"[ture]"));
+        assert_eq!((ErrorKind::InvalidNumber, 1..3), validate_error(b"[1A]"));
+        assert_eq!((ErrorKind::LeadingZero, 3..6), validate_error(b"[1,-01]"));
+        assert_eq!((ErrorKind::InvalidEscape, 3..5), validate_error(br#"["a\g"]"#));
+        assert_eq!((ErrorKind::UncompactedEscape, 2..4), validate_error(br#"["\/"]"#));
+        assert_eq!((ErrorKind::ControlCharacter, 2..3), validate_error(b"[\"\n\"]"));
+    }
+
+    #[test]
+    fn validate_trailing_data() {
+        let mut parsed : Vec<::parse::Node> = Vec::new();
+        let mut stack : Vec<::parse::Stack> = Vec::new();
+        ::parse::parse(b"[1]", &mut parsed, &mut stack).unwrap();
+        let error = super::validate(b"[1]x", &parsed).unwrap_err();
+        assert_eq!(ErrorKind::TrailingData, error.kind());
+        assert_eq!(3..4, error.range());
+    }
+
+    #[test]
+    fn validate_error_location() {
+        let input = br#"{"a":[1,{"b":tru}]}"#;
+        let mut parsed : Vec<::parse::Node> = Vec::new();
+        let mut stack : Vec<::parse::Stack> = Vec::new();
+        ::parse::parse(input, &mut parsed, &mut stack).unwrap();
+        let error = super::validate(input, &parsed).unwrap_err();
+        assert_eq!(ErrorKind::InvalidLiteral, error.kind());
+        assert_eq!(6, error.node());
+        assert_eq!(13..16, error.range());
+        assert_eq!("/a/1/b", error.pointer().to_string());
+        assert_eq!("invalid literal at offset 13 in /a/1/b", error.to_string());
+        let error = ::error::Error::from(error);
+        assert_eq!(13, error.offset());
+
+        let input = br#"{"a":[1}}"#;
+        parsed.clear();
+        ::parse::parse(input, &mut parsed, &mut stack).unwrap();
+        let error = super::validate(input, &parsed).unwrap_err();
+        assert_eq!(ErrorKind::MismatchedBracket, error.kind());
+        assert_eq!(2, error.node());
+        assert_eq!(7..8, error.range());
+        assert_eq!("/a", error.pointer().to_string());
+    }
+
+    #[test]
+    fn validate_all_errors() {
+        let input = br#"[tru,01,"\g",{"a":nul],1.,{"b"@true}}"#;
+        let mut parsed : Vec<::parse::Node> = Vec::new();
+        let mut stack : Vec<::parse::Stack> = Vec::new();
+        ::parse::parse(input, &mut parsed, &mut stack).unwrap();
+        let found: Vec<_> = super::validate_all(input, &parsed).into_iter().map(
+            |diagnostic| (diagnostic.kind(), diagnostic.range(), diagnostic.node().unwrap())
+        ).collect();
+        assert_eq!(vec![
+            (ErrorKind::InvalidLiteral, 1..4, 1),
+            (ErrorKind::LeadingZero, 5..7, 2),
+            (ErrorKind::InvalidEscape, 9..11, 3),
+            (ErrorKind::InvalidLiteral, 18..21, 6),
+            (ErrorKind::MismatchedBracket, 21..22, 4),
+            (ErrorKind::InvalidNumber, 23..25, 7),
+            (ErrorKind::ExpectedColon, 30..31, 9),
+            (ErrorKind::MismatchedBracket, 36..37, 0),
+        ], found);
+        parsed.clear();
+        ::parse::parse(br#"{"a":[1,2]}"#, &mut parsed, &mut stack).unwrap();
+        assert!(super::validate_all(br#"{"a":[1,2]}"#, &parsed).is_empty());
+    }
+
+    fn validate_integers(input: &[u8]) -> Result<(), Vec<usize>> {
+        let mut parsed : Vec<::parse::Node> = Vec::new();
+        let mut stack : Vec<::parse::Stack> = Vec::new();
+        ::parse::parse(input, &mut parsed, &mut stack).unwrap();
+        super::validate_integers(input, parsed.as_slice())
+    }
+
+    #[test]
+    fn validate_safe_integers() {
+        assert_eq!(Ok(()), validate_integers(b"[0,-0,1,-1,123]"));
+        assert_eq!(Ok(()), validate_integers(br#"{"a":[9007199254740991,-9007199254740991]}"#));
+        assert_eq!(Ok(()), validate_integers(br#"["1.5",true,null,{}]"#));
+        assert_eq!(Ok(()), validate_integers(b"[]"));
+    }
+
+    #[test]
+    fn validate_unsafe_integers() {
+        assert_eq!(
+            Err(vec![1, 2]),
+            validate_integers(b"[9007199254740992,-9007199254740992,1]")
+        );
+        assert_eq!(
+            Err(vec![2, 4, 8]),
+            validate_integers(br#"{"a":1.0,"b":1e2,"c":[1,99999999999999999999]}"#)
+        );
+    }
 }
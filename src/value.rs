@@ -0,0 +1,461 @@
+use number::{parse_f64, parse_i64, parse_u64, JsonNumber, NumberError};
+use parse::Node;
+use pointer::resolve;
+use strings::unescaped_eq;
+
+
+/// The type of a JSON value, identified by the first byte of the value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum JsonKind {
+    Object,
+    Array,
+    String,
+    Number,
+    Bool,
+    Null,
+}
+
+/// A reference to a single value in parsed JSON. Pairs the index of the node
+/// in the array of nodes with the offset of the value in the compacted JSON
+/// bytes.
+///
+/// A `ValueRef` is cheap to copy and never allocates. Computing the offset of
+/// a node requires walking the nodes before it, so a `ValueRef` should be
+/// obtained from the root or from another `ValueRef` rather than from a bare
+/// node index.
+#[derive(Clone, Copy, Debug)]
+pub struct ValueRef<'a> {
+    bytes: &'a [u8],
+    nodes: &'a [Node],
+    index: usize,
+    offset: usize,
+}
+
+impl<'a> ValueRef<'a> {
+    /// Get the root object or array of the parsed JSON. Returns `None` if there
+    /// are no nodes.
+    pub fn root(bytes: &'a [u8], nodes: &'a [Node]) -> Option<ValueRef<'a>> {
+        if nodes.is_empty() {
+            return None;
+        }
+        Some(ValueRef::new(bytes, nodes, 0, 0))
+    }
+
+    /// Create a reference to the node at `index` which starts at `offset` in
+    /// the compacted JSON bytes. The caller is responsible for ensuring that
+    /// the offset is actually the offset of the node.
+    pub fn new(
+        bytes: &'a [u8], nodes: &'a [Node], index: usize, offset: usize
+    ) -> ValueRef<'a> {
+        ValueRef { bytes, nodes, index, offset }
+    }
+
+    /// The compacted JSON bytes of the whole document.
+    pub fn document_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// The nodes of the whole document.
+    pub fn document_nodes(&self) -> &'a [Node] {
+        self.nodes
+    }
+
+    /// The index of this node in the array of nodes.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The offset of the first byte of this value in the compacted JSON.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The offset of the byte after the end of this value in the compacted
+    /// JSON.
+    pub fn end_offset(&self) -> usize {
+        self.offset + self.node().length_in_bytes as usize
+    }
+
+    /// The node for this value.
+    pub fn node(&self) -> &'a Node {
+        &self.nodes[self.index]
+    }
+
+    /// The compacted JSON bytes of this value. Strings include the surrounding
+    /// quotes and are still escaped.
+    pub fn raw_bytes(&self) -> &'a [u8] {
+        &self.bytes[self.offset..self.end_offset()]
+    }
+
+    /// The type of this value. This assumes that the JSON is valid, anything
+    /// which isn't an object, array, string, boolean or null is reported as a
+    /// number.
+    pub fn kind(&self) -> JsonKind {
+        match self.bytes[self.offset] {
+            b'{' => JsonKind::Object,
+            b'[' => JsonKind::Array,
+            b'"' => JsonKind::String,
+            b't' | b'f' => JsonKind::Bool,
+            b'n' => JsonKind::Null,
+            _ => JsonKind::Number,
+        }
+    }
+
+    /// Whether this value is an object or an array with nothing in it.
+    pub fn is_empty_container(&self) -> bool {
+        match self.kind() {
+            JsonKind::Object | JsonKind::Array => self.node().children == 0,
+            _ => false,
+        }
+    }
+
+    /// The total number of nodes nested under this value. For an array this
+    /// counts the elements and everything inside them. For an object this
+    /// counts both the keys and the values. Scalars have no children.
+    pub fn children_count(&self) -> usize {
+        self.node().children as usize
+    }
+
+    /// The contents of a string without the surrounding quotes. The contents
+    /// are still escaped. Returns `None` if this value isn't a string.
+    pub fn escaped_str(&self) -> Option<&'a [u8]> {
+        match self.kind() {
+            JsonKind::String => {
+                let raw = self.raw_bytes();
+                Some(&raw[1..raw.len() - 1])
+            },
+            _ => None,
+        }
+    }
+
+    /// Convert a number to an `i64`. Fails if this value isn't a number, if
+    /// it has a fraction or exponent, or if it is out of range.
+    pub fn as_i64(&self) -> Result<i64, NumberError> {
+        parse_i64(self.number_bytes()?)
+    }
+
+    /// Convert a number to a `u64`. Fails if this value isn't a number, if
+    /// it has a fraction or exponent, or if it is out of range.
+    pub fn as_u64(&self) -> Result<u64, NumberError> {
+        parse_u64(self.number_bytes()?)
+    }
+
+    /// Convert a number to the nearest `f64`. Fails if this value isn't a
+    /// number or if it is too large to represent.
+    pub fn as_f64(&self) -> Result<f64, NumberError> {
+        parse_f64(self.number_bytes()?)
+    }
+
+    /// Read a number exactly without rounding it. Fails if this value isn't
+    /// a number.
+    pub fn as_number(&self) -> Result<JsonNumber, NumberError> {
+        JsonNumber::from_bytes(self.number_bytes()?)
+    }
+
+    fn number_bytes(&self) -> Result<&'a [u8], NumberError> {
+        match self.kind() {
+            JsonKind::Number => Ok(self.raw_bytes()),
+            _ => Err(NumberError::Invalid),
+        }
+    }
+
+    /// Look up the value for a key in an object. Returns `None` if this value
+    /// isn't an object or if the object doesn't have the key.
+    /// See `get_bytes`.
+    pub fn get(&self, key: &str) -> Option<ValueRef<'a>> {
+        self.get_bytes(key.as_bytes())
+    }
+
+    /// Look up the value for a key in an object. The key should be plain
+    /// UTF-8, it is compared with the escaped keys in the object without
+    /// decoding them into a separate buffer. If the object has the key more
+    /// than once then the first value is returned.
+    pub fn get_bytes(&self, key: &[u8]) -> Option<ValueRef<'a>> {
+        for (name, value) in self.object_iter()? {
+            if name.escaped_str().map_or(false, |name| unescaped_eq(name, key)) {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Get the element at `index` in an array. Returns `None` if this value
+    /// isn't an array or if the array is too short. This skips over the
+    /// elements before the index so takes time proportional to the index.
+    pub fn get_index(&self, index: usize) -> Option<ValueRef<'a>> {
+        self.array_iter()?.nth(index)
+    }
+
+    /// Look up a value using an RFC 6901 JSON Pointer relative to this value.
+    /// See `pointer::resolve`.
+    pub fn pointer(&self, pointer: &str) -> Option<ValueRef<'a>> {
+        resolve(*self, pointer)
+    }
+
+    /// Iterate over the elements of an array. Returns `None` if this value
+    /// isn't an array.
+    pub fn array_iter(&self) -> Option<ArrayIter<'a>> {
+        match self.kind() {
+            JsonKind::Array => Some(ArrayIter { siblings: self.siblings() }),
+            _ => None,
+        }
+    }
+
+    /// Iterate over the keys and values of an object. Returns `None` if this
+    /// value isn't an object.
+    pub fn object_iter(&self) -> Option<ObjectIter<'a>> {
+        match self.kind() {
+            JsonKind::Object => Some(ObjectIter { siblings: self.siblings() }),
+            _ => None,
+        }
+    }
+
+    /// The direct children of an object or array. The first child is the
+    /// next node in the array and starts at the next byte.
+    fn siblings(&self) -> Siblings<'a> {
+        Siblings {
+            bytes: self.bytes,
+            nodes: self.nodes,
+            index: self.index + 1,
+            offset: self.offset + 1,
+            end: self.index + 1 + self.children_count(),
+        }
+    }
+}
+
+
+/// Walks a run of sibling nodes. The next sibling of a node is `children + 1`
+/// nodes further on in the array and starts `length_in_bytes + 1` bytes
+/// further on in the JSON, skipping over the `b','` or the `b':'` separating
+/// the values.
+#[derive(Clone, Debug)]
+struct Siblings<'a> {
+    bytes: &'a [u8],
+    nodes: &'a [Node],
+    index: usize,
+    offset: usize,
+    end: usize,
+}
+
+impl<'a> Iterator for Siblings<'a> {
+    type Item = ValueRef<'a>;
+
+    fn next(&mut self) -> Option<ValueRef<'a>> {
+        if self.index >= self.end {
+            return None;
+        }
+        let value = ValueRef::new(self.bytes, self.nodes, self.index, self.offset);
+        let node = value.node();
+        self.index += node.children as usize + 1;
+        self.offset += node.length_in_bytes as usize + 1;
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Each sibling uses at least one node.
+        let remaining = self.end.saturating_sub(self.index);
+        (if remaining > 0 { 1 } else { 0 }, Some(remaining))
+    }
+}
+
+
+/// Iterates over the elements of a JSON array.
+///
+/// The nodes only record how far forward the next sibling is so the
+/// iterator can only move forward. Each step is O(1) regardless of the size
+/// of the element being skipped.
+#[derive(Clone, Debug)]
+pub struct ArrayIter<'a> {
+    siblings: Siblings<'a>,
+}
+
+impl<'a> Iterator for ArrayIter<'a> {
+    type Item = ValueRef<'a>;
+
+    fn next(&mut self) -> Option<ValueRef<'a>> {
+        self.siblings.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.siblings.size_hint()
+    }
+}
+
+
+/// Iterates over the keys and values of a JSON object. The keys are
+/// returned as string values and are still escaped.
+///
+/// Like `ArrayIter` this can only move forward and each step is O(1).
+#[derive(Clone, Debug)]
+pub struct ObjectIter<'a> {
+    siblings: Siblings<'a>,
+}
+
+impl<'a> Iterator for ObjectIter<'a> {
+    type Item = (ValueRef<'a>, ValueRef<'a>);
+
+    fn next(&mut self) -> Option<(ValueRef<'a>, ValueRef<'a>)> {
+        let key = self.siblings.next()?;
+        let value = self.siblings.next()?;
+        Some((key, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.siblings.size_hint();
+        (lower, upper.map(|upper| upper / 2))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use document::Document;
+
+    #[test]
+    fn value_root_kind() {
+        let document = Document::from_slice(br#"{"a":[1,true,null]}"#).unwrap();
+        let root = document.root().unwrap();
+        assert_eq!(JsonKind::Object, root.kind());
+        assert_eq!(0, root.index());
+        assert_eq!(0, root.offset());
+        assert_eq!(5, root.children_count());
+        assert_eq!(br#"{"a":[1,true,null]}"#, root.raw_bytes());
+        assert!(!root.is_empty_container());
+    }
+
+    #[test]
+    fn value_kinds() {
+        let document = Document::from_slice(
+            br#"[{},[],"s",-1,0.5,true,false,null]"#
+        ).unwrap();
+        let bytes = document.bytes();
+        let nodes = document.nodes();
+        let expected = [
+            (JsonKind::Object, &b"{}"[..], true),
+            (JsonKind::Array, &b"[]"[..], true),
+            (JsonKind::String, &br#""s""#[..], false),
+            (JsonKind::Number, &b"-1"[..], false),
+            (JsonKind::Number, &b"0.5"[..], false),
+            (JsonKind::Bool, &b"true"[..], false),
+            (JsonKind::Bool, &b"false"[..], false),
+            (JsonKind::Null, &b"null"[..], false),
+        ];
+        let mut offset = 1;
+        for (index, &(kind, raw, empty)) in expected.iter().enumerate() {
+            let value = ValueRef::new(bytes, nodes, index + 1, offset);
+            assert_eq!(kind, value.kind());
+            assert_eq!(raw, value.raw_bytes());
+            assert_eq!(empty, value.is_empty_container());
+            assert_eq!(0, value.children_count());
+            offset = value.end_offset() + 1;
+        }
+    }
+
+    #[test]
+    fn value_array_iter() {
+        let document = Document::from_slice(br#"[[1,[2]],{"a":3},4]"#).unwrap();
+        let root = document.root().unwrap();
+        assert!(root.object_iter().is_none());
+        let elements: Vec<&[u8]> = root.array_iter().unwrap()
+            .map(|value| value.raw_bytes())
+            .collect();
+        assert_eq!(vec![
+            &b"[1,[2]]"[..], &br#"{"a":3}"#[..], &b"4"[..],
+        ], elements);
+        let indices: Vec<usize> = root.array_iter().unwrap()
+            .map(|value| value.index())
+            .collect();
+        assert_eq!(vec![1, 5, 8], indices);
+    }
+
+    #[test]
+    fn value_object_iter() {
+        let document = Document::from_slice(
+            br#"{"a":{"b":[]},"c":"d","e":{}}"#
+        ).unwrap();
+        let root = document.root().unwrap();
+        assert!(root.array_iter().is_none());
+        let members: Vec<(&[u8], &[u8])> = root.object_iter().unwrap()
+            .map(|(key, value)| (key.raw_bytes(), value.raw_bytes()))
+            .collect();
+        assert_eq!(vec![
+            (&br#""a""#[..], &br#"{"b":[]}"#[..]),
+            (&br#""c""#[..], &br#""d""#[..]),
+            (&br#""e""#[..], &b"{}"[..]),
+        ], members);
+    }
+
+    #[test]
+    fn value_iter_empty() {
+        let document = Document::from_slice(b"[[],{}]").unwrap();
+        let mut elements = document.root().unwrap().array_iter().unwrap();
+        assert_eq!(0, elements.next().unwrap().array_iter().unwrap().count());
+        assert_eq!(0, elements.next().unwrap().object_iter().unwrap().count());
+        assert!(elements.next().is_none());
+    }
+
+    #[test]
+    fn value_get() {
+        let document = Document::from_slice(
+            br#"{"type":"m.text","content":{"body":"hi"},"a\"b":1,"\u00e9":2}"#
+        ).unwrap();
+        let root = document.root().unwrap();
+        assert_eq!(br#""m.text""#, root.get("type").unwrap().raw_bytes());
+        let content = root.get("content").unwrap();
+        assert_eq!(br#""hi""#, content.get("body").unwrap().raw_bytes());
+        assert_eq!(b"1", root.get("a\"b").unwrap().raw_bytes());
+        assert_eq!(b"2", root.get("\u{e9}").unwrap().raw_bytes());
+        assert_eq!(b"2", root.get_bytes(b"\xC3\xA9").unwrap().raw_bytes());
+        assert!(root.get("missing").is_none());
+        assert!(root.get("m.text").is_none());
+        assert!(content.get("body").unwrap().get("body").is_none());
+    }
+
+    #[test]
+    fn value_get_index() {
+        let document = Document::from_slice(br#"[[1,2],{"a":3},4]"#).unwrap();
+        let root = document.root().unwrap();
+        assert_eq!(b"[1,2]", root.get_index(0).unwrap().raw_bytes());
+        assert_eq!(b"4", root.get_index(2).unwrap().raw_bytes());
+        assert_eq!(b"2", root.get_index(0).unwrap().get_index(1).unwrap().raw_bytes());
+        assert!(root.get_index(3).is_none());
+        assert!(root.get_index(1).unwrap().get_index(0).is_none());
+    }
+
+    #[test]
+    fn value_numbers() {
+        let document = Document::from_slice(
+            br#"[-12, 18446744073709551615, 2.5e-3, "1"]"#
+        ).unwrap();
+        let root = document.root().unwrap();
+        let small = root.get_index(0).unwrap();
+        assert_eq!(Ok(-12), small.as_i64());
+        assert_eq!(Err(NumberError::Overflow), small.as_u64());
+        assert_eq!(Ok(-12.0), small.as_f64());
+        let large = root.get_index(1).unwrap();
+        assert_eq!(Err(NumberError::Overflow), large.as_i64());
+        assert_eq!(Ok(u64::MAX), large.as_u64());
+        let fraction = root.get_index(2).unwrap();
+        assert_eq!(Err(NumberError::NotAnInteger), fraction.as_i64());
+        assert_eq!(Ok(0.0025), fraction.as_f64());
+        assert_eq!("0.0025", fraction.as_number().unwrap().to_decimal_string());
+        let string = root.get_index(3).unwrap();
+        assert_eq!(Err(NumberError::Invalid), string.as_i64());
+        assert_eq!(Err(NumberError::Invalid), string.as_f64());
+        assert!(string.as_number().is_err());
+    }
+
+    #[test]
+    fn value_escaped_str() {
+        let document = Document::from_slice(br#"["a\nb",1]"#).unwrap();
+        let mut elements = document.root().unwrap().array_iter().unwrap();
+        assert_eq!(Some(&br#"a\nb"#[..]), elements.next().unwrap().escaped_str());
+        assert_eq!(None, elements.next().unwrap().escaped_str());
+    }
+
+    #[test]
+    fn value_root_empty() {
+        assert!(ValueRef::root(b"", &[]).is_none());
+    }
+}
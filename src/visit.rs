@@ -0,0 +1,196 @@
+use value::{ArrayIter, JsonKind, ObjectIter, ValueRef};
+
+
+/// Returned when entering an object or array to say whether to visit the
+/// values inside it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Visit {
+    /// Visit the values inside the object or array.
+    Continue,
+    /// Skip over the values inside the object or array. Skipping is cheap
+    /// because the node records how many nodes are nested inside it.
+    Skip,
+}
+
+/// Callbacks for a depth first traversal of parsed JSON using `walk`.
+///
+/// Every method has a default implementation which does nothing, so a
+/// visitor only needs to implement the callbacks it is interested in.
+pub trait Visitor<'a> {
+    /// Called at the start of an object, before any of its keys or values.
+    fn enter_object(&mut self, _object: ValueRef<'a>) -> Visit {
+        Visit::Continue
+    }
+
+    /// Called with each key in an object, before visiting its value.
+    fn key(&mut self, _key: ValueRef<'a>) {}
+
+    /// Called at the start of an array, before any of its elements.
+    fn enter_array(&mut self, _array: ValueRef<'a>) -> Visit {
+        Visit::Continue
+    }
+
+    /// Called with each string, number, boolean or null value.
+    fn scalar(&mut self, _value: ValueRef<'a>) {}
+
+    /// Called at the end of an object. This is called even if entering the
+    /// object returned `Visit::Skip`.
+    fn exit_object(&mut self, _object: ValueRef<'a>) {}
+
+    /// Called at the end of an array. This is called even if entering the
+    /// array returned `Visit::Skip`.
+    fn exit_array(&mut self, _array: ValueRef<'a>) {}
+}
+
+/// An object or array that the walk is inside.
+enum Frame<'a> {
+    Object(ValueRef<'a>, ObjectIter<'a>),
+    Array(ValueRef<'a>, ArrayIter<'a>),
+}
+
+/// Walk a value and everything inside it in document order, calling the
+/// visitor for each value. The walk uses an explicit stack rather than
+/// recursion so deeply nested JSON can't overflow the call stack.
+pub fn walk<'a, V: Visitor<'a>>(root: ValueRef<'a>, visitor: &mut V) {
+    let mut stack: Vec<Frame<'a>> = Vec::new();
+    start(root, visitor, &mut stack);
+    loop {
+        let next = match stack.last_mut() {
+            None => return,
+            Some(&mut Frame::Array(_, ref mut elements)) => elements.next(),
+            Some(&mut Frame::Object(_, ref mut members)) => {
+                members.next().map(|(key, value)| {
+                    visitor.key(key);
+                    value
+                })
+            },
+        };
+        match next {
+            Some(value) => start(value, visitor, &mut stack),
+            None => match stack.pop() {
+                Some(Frame::Object(object, _)) => visitor.exit_object(object),
+                Some(Frame::Array(array, _)) => visitor.exit_array(array),
+                None => return,
+            },
+        }
+    }
+}
+
+/// Visit a scalar, or enter an object or array and push it onto the stack.
+fn start<'a, V: Visitor<'a>>(
+    value: ValueRef<'a>, visitor: &mut V, stack: &mut Vec<Frame<'a>>
+) {
+    match value.kind() {
+        JsonKind::Object => match visitor.enter_object(value) {
+            Visit::Continue => {
+                stack.push(Frame::Object(value, value.object_iter().unwrap()));
+            },
+            Visit::Skip => visitor.exit_object(value),
+        },
+        JsonKind::Array => match visitor.enter_array(value) {
+            Visit::Continue => {
+                stack.push(Frame::Array(value, value.array_iter().unwrap()));
+            },
+            Visit::Skip => visitor.exit_array(value),
+        },
+        _ => visitor.scalar(value),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use document::Document;
+
+    /// Records the callbacks as a string. Skips any object with a "skip" key.
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    fn text(value: ValueRef) -> String {
+        String::from_utf8(value.raw_bytes().to_vec()).unwrap()
+    }
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn enter_object(&mut self, object: ValueRef<'a>) -> Visit {
+            self.events.push("{".to_string());
+            match object.get("skip") {
+                Some(_) => Visit::Skip,
+                None => Visit::Continue,
+            }
+        }
+
+        fn key(&mut self, key: ValueRef<'a>) {
+            self.events.push(format!("{}:", text(key)));
+        }
+
+        fn enter_array(&mut self, _array: ValueRef<'a>) -> Visit {
+            self.events.push("[".to_string());
+            Visit::Continue
+        }
+
+        fn scalar(&mut self, value: ValueRef<'a>) {
+            self.events.push(text(value));
+        }
+
+        fn exit_object(&mut self, _object: ValueRef<'a>) {
+            self.events.push("}".to_string());
+        }
+
+        fn exit_array(&mut self, _array: ValueRef<'a>) {
+            self.events.push("]".to_string());
+        }
+    }
+
+    fn record(input: &[u8]) -> String {
+        let document = Document::from_slice(input).unwrap();
+        let mut recorder = Recorder { events: Vec::new() };
+        walk(document.root().unwrap(), &mut recorder);
+        recorder.events.join(" ")
+    }
+
+    #[test]
+    fn walk_nested() {
+        assert_eq!(
+            r#"{ "a": [ 1 { "b": null } [ ] ] "c": true }"#,
+            record(br#"{"a":[1,{"b":null},[]],"c":true}"#)
+        );
+    }
+
+    #[test]
+    fn walk_skip() {
+        assert_eq!(
+            r#"[ { } { "c": 1 } ]"#,
+            record(br#"[{"skip":[1,2,{"x":3}]},{"c":1}]"#)
+        );
+    }
+
+    #[test]
+    fn walk_empty() {
+        assert_eq!("[ ]", record(b"[]"));
+        assert_eq!("{ }", record(b"{}"));
+    }
+
+    #[test]
+    fn walk_deeply_nested() {
+        let mut input = vec![b'['; 100000];
+        input.extend_from_slice(&vec![b']'; 100000]);
+        let document = Document::from_slice(&input).unwrap();
+        struct Depth { depth: usize, max: usize }
+        impl<'a> Visitor<'a> for Depth {
+            fn enter_array(&mut self, _array: ValueRef<'a>) -> Visit {
+                self.depth += 1;
+                self.max = self.max.max(self.depth);
+                Visit::Continue
+            }
+            fn exit_array(&mut self, _array: ValueRef<'a>) {
+                self.depth -= 1;
+            }
+        }
+        let mut visitor = Depth { depth: 0, max: 0 };
+        walk(document.root().unwrap(), &mut visitor);
+        assert_eq!(100000, visitor.max);
+        assert_eq!(0, visitor.depth);
+    }
+}